@@ -0,0 +1,265 @@
+use error::VmError;
+use symbol_table::{Address, SymbolTable};
+
+#[derive(Debug)]
+pub struct Assembler {
+    symbol_table: SymbolTable,
+    labels: Vec<(String, u16)>,
+    variables: Vec<(String, u16)>,
+}
+
+impl Assembler {
+    pub const PREDEFINED: &'static [(&'static str, u16)] = &[
+        ("SP", 0),
+        ("LCL", 1),
+        ("ARG", 2),
+        ("THIS", 3),
+        ("THAT", 4),
+        ("R0", 0),
+        ("R1", 1),
+        ("R2", 2),
+        ("R3", 3),
+        ("R4", 4),
+        ("R5", 5),
+        ("R6", 6),
+        ("R7", 7),
+        ("R8", 8),
+        ("R9", 9),
+        ("R10", 10),
+        ("R11", 11),
+        ("R12", 12),
+        ("R13", 13),
+        ("R14", 14),
+        ("R15", 15),
+        ("SCREEN", 16384),
+        ("KBD", 24576),
+    ];
+
+    // comp mnemonic -> a-bit followed by the six c-bits
+    const COMP: &'static [(&'static str, &'static str)] = &[
+        ("0", "0101010"),
+        ("1", "0111111"),
+        ("-1", "0111010"),
+        ("D", "0001100"),
+        ("A", "0110000"),
+        ("!D", "0001101"),
+        ("!A", "0110001"),
+        ("-D", "0001111"),
+        ("-A", "0110011"),
+        ("D+1", "0011111"),
+        ("A+1", "0110111"),
+        ("D-1", "0001110"),
+        ("A-1", "0110010"),
+        ("D+A", "0000010"),
+        ("A+D", "0000010"),
+        ("D-A", "0010011"),
+        ("A-D", "0000111"),
+        ("D&A", "0000000"),
+        ("A&D", "0000000"),
+        ("D|A", "0010101"),
+        ("A|D", "0010101"),
+        ("M", "1110000"),
+        ("!M", "1110001"),
+        ("-M", "1110011"),
+        ("M+1", "1110111"),
+        ("M-1", "1110010"),
+        ("D+M", "1000010"),
+        ("M+D", "1000010"),
+        ("D-M", "1010011"),
+        ("M-D", "1000111"),
+        ("D&M", "1000000"),
+        ("M&D", "1000000"),
+        ("D|M", "1010101"),
+        ("M|D", "1010101"),
+    ];
+
+    const JUMP: &'static [(&'static str, &'static str)] = &[
+        ("", "000"),
+        ("JGT", "001"),
+        ("JEQ", "010"),
+        ("JGE", "011"),
+        ("JLT", "100"),
+        ("JNE", "101"),
+        ("JLE", "110"),
+        ("JMP", "111"),
+    ];
+
+    pub fn new() -> Assembler {
+        let mut symbol_table = SymbolTable::new();
+        for entry in Assembler::PREDEFINED {
+            symbol_table.add_entry(entry.0, Address::Absolute(entry.1));
+        }
+        Assembler { symbol_table, labels: vec![], variables: vec![] }
+    }
+
+    pub fn assemble(&mut self, asm: &str) -> Result<String, VmError> {
+        let instructions: Vec<&str> = asm.lines().filter_map(Assembler::clean_line).collect();
+
+        //First pass: labels resolve to the ROM address of the next instruction
+        let mut rom_address: u16 = 0;
+        for instruction in &instructions {
+            if instruction.starts_with('(') {
+                let label = instruction.trim_start_matches('(').trim_end_matches(')');
+                self.symbol_table.add_entry(label, Address::Absolute(rom_address));
+                self.labels.push((String::from(label), rom_address));
+            } else {
+                rom_address += 1;
+            }
+        }
+
+        //Second pass: encode everything that isn't a label
+        let mut out = String::new();
+        for instruction in instructions {
+            if instruction.starts_with('(') {
+                continue;
+            }
+            let code = if let Some(symbol) = instruction.strip_prefix('@') {
+                self.encode_a(symbol)?
+            } else {
+                Assembler::encode_c(instruction)?
+            };
+            out.push_str(&code);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    fn clean_line(line: &str) -> Option<&str> {
+        //Strips comments and whitespace, skipping lines with nothing left
+        let code = match line.find("//") {
+            Some(i) => &line[..i],
+            None => line,
+        }
+        .trim();
+        if code.is_empty() {
+            None
+        } else {
+            Some(code)
+        }
+    }
+
+    fn encode_a(&mut self, symbol: &str) -> Result<String, VmError> {
+        let value = match symbol.parse::<u16>() {
+            Ok(value) => value,
+            Err(_) => self.resolve(symbol),
+        };
+        if value > 32767 {
+            return Err(VmError::InvalidInstruction(format!("@{}", symbol)));
+        }
+        Ok(format!("{:016b}", value))
+    }
+
+    fn resolve(&mut self, symbol: &str) -> u16 {
+        //Unknown symbols are variables, allocated from RAM[16] upward
+        if let Some(Address::Absolute(addr)) = self.symbol_table.get_address(symbol) {
+            return *addr;
+        }
+        let addr = self.symbol_table.allocate(symbol);
+        self.variables.push((String::from(symbol), addr));
+        addr
+    }
+
+    //The .sym sidecar: every label with its ROM address, then every variable with its RAM
+    //address, one per line in the order they were found
+    pub fn symbols(&self) -> String {
+        let mut out = String::new();
+        for (label, addr) in &self.labels {
+            out.push_str(&format!("ROM\t{}\t{}\n", label, addr));
+        }
+        for (variable, addr) in &self.variables {
+            out.push_str(&format!("RAM\t{}\t{}\n", variable, addr));
+        }
+        out
+    }
+
+    fn encode_c(instruction: &str) -> Result<String, VmError> {
+        let (dest, rest) = match instruction.find('=') {
+            Some(i) => (&instruction[..i], &instruction[i + 1..]),
+            None => ("", instruction),
+        };
+        let (comp, jump) = match rest.find(';') {
+            Some(i) => (&rest[..i], &rest[i + 1..]),
+            None => (rest, ""),
+        };
+        let invalid = || VmError::InvalidInstruction(String::from(instruction));
+
+        let comp_bits = Assembler::lookup(Assembler::COMP, comp).ok_or_else(invalid)?;
+        let jump_bits = Assembler::lookup(Assembler::JUMP, jump).ok_or_else(invalid)?;
+        let mut dest_bits = 0;
+        for c in dest.chars() {
+            dest_bits |= match c {
+                'A' => 4,
+                'D' => 2,
+                'M' => 1,
+                _ => return Err(invalid()),
+            };
+        }
+        Ok(format!("111{}{:03b}{}", comp_bits, dest_bits, jump_bits))
+    }
+
+    fn lookup(table: &[(&str, &'static str)], key: &str) -> Option<&'static str> {
+        table.iter().find(|entry| entry.0 == key).map(|entry| entry.1)
+    }
+}
+
+impl Default for Assembler {
+    fn default() -> Assembler {
+        Assembler::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn assemble_a_instruction() {
+        let mut assembler = Assembler::new();
+        assert_eq!(assembler.assemble("@2").unwrap(), "0000000000000010\n");
+    }
+
+    #[test]
+    fn assemble_c_instructions() {
+        let mut assembler = Assembler::new();
+        assert_eq!(
+            assembler.assemble("D=A\n0;JMP\nAM=M-1\n").unwrap(),
+            "1110110000010000\n1110101010000111\n1111110010101000\n"
+        );
+    }
+
+    #[test]
+    fn assemble_labeled_jump() {
+        let mut assembler = Assembler::new();
+        let asm = "//Loop forever\n@0\nD=A\n(LOOP)\n@LOOP\n0;JMP\n";
+        assert_eq!(
+            assembler.assemble(asm).unwrap(),
+            "0000000000000000\n1110110000010000\n0000000000000010\n1110101010000111\n"
+        );
+    }
+
+    #[test]
+    fn assemble_variables_and_predefined() {
+        let mut assembler = Assembler::new();
+        assert_eq!(
+            assembler.assemble("@i\n@j\n@i\n@SP\n@R13\n").unwrap(),
+            "0000000000010000\n0000000000010001\n0000000000010000\n0000000000000000\n0000000000001101\n"
+        );
+    }
+
+    #[test]
+    fn symbols_list_labels_and_variables() {
+        let mut assembler = Assembler::new();
+        assembler.assemble("@i\nM=0\n(LOOP)\n@j\n@LOOP\n0;JMP\n(END)\n").unwrap();
+        assert_eq!(
+            assembler.symbols(),
+            "ROM\tLOOP\t2\nROM\tEND\t5\nRAM\ti\t16\nRAM\tj\t17\n"
+        );
+    }
+
+    #[test]
+    fn assemble_invalid_instruction() {
+        let mut assembler = Assembler::new();
+        assert!(matches!(assembler.assemble("D=Q"), Err(VmError::InvalidInstruction(_))));
+        assert!(matches!(assembler.assemble("@40000"), Err(VmError::InvalidInstruction(_))));
+    }
+}
@@ -0,0 +1,108 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum VmError {
+    InvalidSegment { segment: String, file: String },
+    PopToConstant { file: String },
+    UnknownArithmetic { file: String, line: usize },
+    IndexOutOfRange { segment: String, index: u16, file: String },
+    Argument { command: String, file: String, line: usize },
+    Keyword { file: String, line: usize },
+    IndexOverflow { value: String, file: String, line: usize },
+    ConstantOutOfRange { value: u16, file: String, line: usize },
+    NegativeIndex { value: String, file: String, line: usize },
+    CountOutOfRange { value: u16, limit: u16, file: String, line: usize },
+    UndefinedToken { word: String, file: String, line: usize },
+    InvalidInstruction(String),
+    InvalidRule { line: usize, text: String },
+    UndefinedLabel { label: String, file: String, line: usize },
+    UndefinedFunction { symbol: String, file: String, line: usize },
+    StaticOverflow(String),
+    Runtime { index: usize, message: String },
+    Io(io::Error),
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VmError::InvalidSegment { segment, file } => {
+                write!(f, "{}: Invalid segment provided: {}", file, segment)
+            }
+            VmError::PopToConstant { file } => write!(f, "{}: Cannot pop to constant", file),
+            VmError::UnknownArithmetic { file, line } => {
+                write!(f, "{}: Invalid arithmetic command at line {}", file, line)
+            }
+            VmError::IndexOutOfRange { segment, index, file } => write!(
+                f,
+                "{}: Index {} is out of range for the {} segment",
+                file, index, segment
+            ),
+            VmError::Argument { command, file, line } => write!(
+                f,
+                "{}: Improper arguments for {} command at line {}",
+                file, command, line
+            ),
+            VmError::Keyword { file, line } => write!(f, "{}: Expected keyword at line {}", file, line),
+            VmError::IndexOverflow { value, file, line } => {
+                write!(f, "{}: Index {} at line {} is too large", file, value, line)
+            }
+            VmError::ConstantOutOfRange { value, file, line } => write!(
+                f,
+                "{}: Constant {} at line {} is above 32767",
+                file, value, line
+            ),
+            VmError::NegativeIndex { value, file, line } => {
+                write!(f, "{}: Index {} at line {} can't be negative", file, value, line)
+            }
+            VmError::CountOutOfRange { value, limit, file, line } => write!(
+                f,
+                "{}: Count {} at line {} is above the limit of {}",
+                file, value, line, limit
+            ),
+            VmError::UndefinedToken { word, file, line } => {
+                write!(f, "{}: Unrecognized token {} at line {}", file, word, line)
+            }
+            VmError::InvalidInstruction(instruction) => {
+                write!(f, "Invalid assembly instruction: {}", instruction)
+            }
+            VmError::InvalidRule { line, text } => write!(f, "Invalid token rule at line {}: {}", line, text),
+            VmError::UndefinedLabel { label, file, line } => {
+                write!(f, "{}: Jump to undefined label {} at line {}", file, label, line)
+            }
+            VmError::UndefinedFunction { symbol, file, line } => {
+                write!(f, "{}: Call to undefined function {} at line {}", file, symbol, line)
+            }
+            VmError::StaticOverflow(symbol) => {
+                write!(f, "Out of static memory: {} would be placed at RAM[256]", symbol)
+            }
+            VmError::Runtime { index, message } => {
+                write!(f, "Runtime error at command {}: {}", index, message)
+            }
+            VmError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for VmError {}
+
+impl VmError {
+    //The tokenizer only sees one line of text and the validator only sees commands, so
+    //their errors get their file and line here
+    pub fn located(self, file: &str, line: usize) -> VmError {
+        let file = String::from(file);
+        match self {
+            VmError::UndefinedToken { word, .. } => VmError::UndefinedToken { word, file, line },
+            VmError::UndefinedLabel { label, .. } => VmError::UndefinedLabel { label, file, line },
+            VmError::UndefinedFunction { symbol, .. } => VmError::UndefinedFunction { symbol, file, line },
+            other => other,
+        }
+    }
+}
+
+impl From<io::Error> for VmError {
+    fn from(err: io::Error) -> VmError {
+        VmError::Io(err)
+    }
+}
@@ -0,0 +1,677 @@
+use error::VmError;
+use parser::Command;
+use symbol_table::{Address, SymbolTable};
+use std::collections::HashMap;
+use tokenizer::TokenType;
+use validator::scoped_label;
+
+const SP: usize = 0;
+const LCL: usize = 1;
+const ARG: usize = 2;
+const THIS: usize = 3;
+const THAT: usize = 4;
+
+//The stack starts here, and by default runs up to where the heap begins
+const STACK_BASE: usize = 256;
+const HEAP_BASE: usize = 2048;
+const SCREEN_BASE: usize = 16384;
+
+//Runs VM commands directly against a simulated Hack RAM, laid out the same way the
+//translated assembly would use it
+#[derive(Debug)]
+pub struct Vm {
+    ram: Vec<i16>,
+    commands: Vec<Command>,
+    pc: usize,
+    //Goto and if-goto targets, keyed by the index of the jumping command
+    targets: HashMap<usize, usize>,
+    functions: HashMap<String, usize>,
+    call_stack: Vec<usize>,
+    symbol_table: SymbolTable,
+    step_limit: usize,
+    //First address past the stack. Pushing into it is an overflow
+    stack_top: usize,
+}
+
+impl Vm {
+    pub fn new(commands: Vec<Command>) -> Result<Vm, VmError> {
+        //Labels are scoped to the function they appear in, the same way the writer scopes them
+        let mut labels: HashMap<String, usize> = HashMap::new();
+        let mut functions: HashMap<String, usize> = HashMap::new();
+        let mut current_function: Option<&str> = None;
+        for (i, command) in commands.iter().enumerate() {
+            match command {
+                Command::Function { symbol, .. } => {
+                    functions.insert(symbol.clone(), i);
+                    current_function = Some(symbol);
+                }
+                Command::Label(label) => {
+                    labels.insert(scoped_label(current_function, label), i);
+                }
+                _ => {}
+            }
+        }
+
+        let mut targets: HashMap<usize, usize> = HashMap::new();
+        current_function = None;
+        for (i, command) in commands.iter().enumerate() {
+            match command {
+                Command::Function { symbol, .. } => current_function = Some(symbol),
+                Command::Goto(label) | Command::If(label) => {
+                    let scoped = scoped_label(current_function, label);
+                    match labels.get(&scoped) {
+                        Some(target) => targets.insert(i, *target),
+                        None => return Err(VmError::UndefinedLabel { label: scoped, file: String::new(), line: 0 }),
+                    };
+                }
+                _ => {}
+            }
+        }
+
+        let mut ram = vec![0; 32768];
+        ram[SP] = STACK_BASE as i16;
+        Ok(Vm {
+            ram,
+            commands,
+            pc: 0,
+            targets,
+            functions,
+            call_stack: vec![],
+            symbol_table: SymbolTable::new(),
+            step_limit: 1_000_000,
+            stack_top: HEAP_BASE,
+        })
+    }
+
+    //Does what the translator's bootstrap does: call Sys.init, halting when it returns
+    pub fn bootstrap(&mut self) -> Result<(), VmError> {
+        self.pc = self.commands.len();
+        self.call("Sys.init", 0)
+    }
+
+    //Programs like Sys.init usually loop forever, so run gives up after this many steps
+    pub fn set_step_limit(&mut self, step_limit: usize) {
+        self.step_limit = step_limit;
+    }
+
+    //Lets the stack grow past the heap base, up to the screen at most
+    pub fn set_stack_top(&mut self, stack_top: usize) {
+        self.stack_top = stack_top.min(SCREEN_BASE);
+    }
+
+    //Runs until the program falls off the end of the command list
+    pub fn run(&mut self) -> Result<(), VmError> {
+        let mut steps = 0;
+        while !self.halted() {
+            if steps == self.step_limit {
+                return Err(VmError::Runtime {
+                    index: self.pc,
+                    message: format!("Gave up after {} steps", steps),
+                });
+            }
+            self.step()?;
+            steps += 1;
+        }
+        Ok(())
+    }
+
+    pub fn halted(&self) -> bool {
+        self.pc >= self.commands.len()
+    }
+
+    pub fn ram(&self) -> &[i16] {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut [i16] {
+        &mut self.ram
+    }
+
+    //Everything pushed above the stack base, bottom first
+    pub fn stack(&self) -> &[i16] {
+        let sp = self.ram[SP] as usize;
+        if sp <= STACK_BASE {
+            return &[];
+        }
+        &self.ram[STACK_BASE..sp]
+    }
+
+    pub fn sp(&self) -> i16 {
+        self.ram[SP]
+    }
+
+    //Index of the next command to run
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn current_command(&self) -> Option<&Command> {
+        self.commands.get(self.pc)
+    }
+
+    //Up to n values from the top of the stack, topmost last
+    pub fn top(&self, n: usize) -> &[i16] {
+        let stack = self.stack();
+        &stack[stack.len().saturating_sub(n)..]
+    }
+
+    //The LCL, ARG, THIS and THAT base pointers, in that order
+    pub fn segment_bases(&self) -> [i16; 4] {
+        [self.ram[LCL], self.ram[ARG], self.ram[THIS], self.ram[THAT]]
+    }
+
+    //Runs the next command. Does nothing once the program has halted
+    pub fn step(&mut self) -> Result<(), VmError> {
+        if self.halted() {
+            return Ok(());
+        }
+        let index = self.pc;
+        self.pc += 1;
+        match self.commands[index].clone() {
+            Command::Push { segment, index, class_name } => {
+                let value = self.read_segment(&segment, index, &class_name)?;
+                self.push(value)?;
+            }
+            Command::Pop { segment, index, class_name } => {
+                let value = self.pop()?;
+                self.write_segment(&segment, index, &class_name, value)?;
+            }
+            Command::Store { segment, index, value, class_name } => {
+                self.write_segment(&segment, index, &class_name, value as i16)?;
+            }
+            Command::Arithmetic(op) => self.arithmetic(op)?,
+            Command::Label(_) => {}
+            Command::Goto(_) => self.pc = self.targets[&index],
+            Command::If(_) => {
+                if self.pop()? != 0 {
+                    self.pc = self.targets[&index];
+                }
+            }
+            Command::Function { nvars, .. } => {
+                for _ in 0..nvars {
+                    self.push(0)?;
+                }
+            }
+            Command::Call { symbol, nargs } => self.call(&symbol, nargs)?,
+            Command::Return => self.ret()?,
+            Command::TailCall { symbol, nargs } => self.tail_call(&symbol, nargs)?,
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, symbol: &str, nargs: u16) -> Result<(), VmError> {
+        let target = match self.functions.get(symbol) {
+            Some(target) => *target,
+            None => return Err(VmError::UndefinedFunction { symbol: String::from(symbol), file: String::new(), line: 0 }),
+        };
+        //The frame matches the generated assembly. The return address is also kept on
+        //call_stack, since a command index doesn't always fit in RAM
+        let return_address = self.pc;
+        self.call_stack.push(return_address);
+        self.push(return_address as i16)?;
+        for register in &[LCL, ARG, THIS, THAT] {
+            let value = self.ram[*register];
+            self.push(value)?;
+        }
+        let sp = self.ram[SP];
+        self.ram[ARG] = sp - 5 - nargs as i16;
+        self.ram[LCL] = sp;
+        self.pc = target;
+        Ok(())
+    }
+
+    fn tail_call(&mut self, symbol: &str, nargs: u16) -> Result<(), VmError> {
+        let target = match self.functions.get(symbol) {
+            Some(target) => *target,
+            None => return Err(VmError::UndefinedFunction { symbol: String::from(symbol), file: String::new(), line: 0 }),
+        };
+        //Outside any call there is no frame to reuse. A plain call does the same job, and
+        //halting when the callee returns is what the return this replaced would have done
+        if self.call_stack.is_empty() {
+            self.pc = self.commands.len();
+            return self.call(symbol, nargs);
+        }
+        //The new arguments and the current frame (return address, then the caller's
+        //segments) move down to where the current arguments start, so the callee
+        //returns straight to our caller. call_stack already has that return address
+        let args = self.ram_address(self.ram[SP], -(nargs as isize))?;
+        let frame = self.ram_address(self.ram[LCL], -5)?;
+        let mut moved: Vec<i16> = self.ram[args..args + nargs as usize].to_vec();
+        moved.extend_from_slice(&self.ram[frame..frame + 5]);
+        let arg = self.ram_address(self.ram[ARG], 0)?;
+        self.ram_address(self.ram[ARG], moved.len() as isize - 1)?;
+        self.ram[arg..arg + moved.len()].copy_from_slice(&moved);
+        self.ram[SP] = (arg + moved.len()) as i16;
+        self.ram[LCL] = self.ram[SP];
+        self.pc = target;
+        Ok(())
+    }
+
+    fn ret(&mut self) -> Result<(), VmError> {
+        //Returning from the outermost function ends the program. Without a bootstrap that
+        //function was never called, so there is no frame to restore
+        let return_address = match self.call_stack.last() {
+            Some(return_address) => *return_address,
+            None => {
+                self.pc = self.commands.len();
+                return Ok(());
+            }
+        };
+        //The saved LCL, ARG, THIS and THAT sit just below the frame
+        let saved = self.ram_address(self.ram[LCL], -4)?;
+        self.ram_address(self.ram[LCL], -1)?;
+        let arg = self.ram_address(self.ram[ARG], 0)?;
+        let value = self.pop()?;
+        self.ram[arg] = value;
+        self.ram[SP] = arg as i16 + 1;
+        self.ram[THAT] = self.ram[saved + 3];
+        self.ram[THIS] = self.ram[saved + 2];
+        self.ram[ARG] = self.ram[saved + 1];
+        self.ram[LCL] = self.ram[saved];
+        self.call_stack.pop();
+        self.pc = return_address;
+        Ok(())
+    }
+
+    fn arithmetic(&mut self, op: TokenType) -> Result<(), VmError> {
+        let result = match op {
+            TokenType::Negate => self.pop()?.wrapping_neg(),
+            TokenType::Not => !self.pop()?,
+            TokenType::ShiftLeft => self.pop()?.wrapping_shl(1),
+            TokenType::ShiftRight => ((self.pop()? as u16) >> 1) as i16,
+            _ => {
+                let y = self.pop()?;
+                let x = self.pop()?;
+                match op {
+                    TokenType::Add => x.wrapping_add(y),
+                    TokenType::Subtract => x.wrapping_sub(y),
+                    TokenType::And => x & y,
+                    TokenType::Or => x | y,
+                    TokenType::Equal => -((x == y) as i16),
+                    TokenType::GreaterThan => -((x > y) as i16),
+                    TokenType::LessThan => -((x < y) as i16),
+                    _ => return Err(self.runtime_error(format!("Invalid arithmetic command {:?}", op))),
+                }
+            }
+        };
+        self.push(result)
+    }
+
+    fn address(&mut self, segment: &str, index: u16, class_name: &str) -> Result<usize, VmError> {
+        let offset = index as isize;
+        let address = match segment {
+            //The pointer registers can hold anything, so the sum is checked against RAM
+            "local" => self.ram_address(self.ram[LCL], offset)?,
+            "argument" => self.ram_address(self.ram[ARG], offset)?,
+            "this" => self.ram_address(self.ram[THIS], offset)?,
+            "that" => self.ram_address(self.ram[THAT], offset)?,
+            "temp" if index < 8 => 5 + index as usize,
+            "ram" if index <= 24576 => index as usize,
+            "pointer" if index < 2 => THIS + index as usize,
+            "static" => self.static_address(class_name, index)? as usize,
+            "temp" | "pointer" | "ram" => {
+                return Err(VmError::IndexOutOfRange {
+                    segment: String::from(segment),
+                    index,
+                    file: String::from(class_name),
+                })
+            }
+            _ => {
+                return Err(VmError::InvalidSegment {
+                    segment: String::from(segment),
+                    file: String::from(class_name),
+                })
+            }
+        };
+        Ok(address)
+    }
+
+    fn read_segment(&mut self, segment: &str, index: u16, class_name: &str) -> Result<i16, VmError> {
+        if segment == "constant" {
+            return Ok(index as i16);
+        }
+        let address = self.address(segment, index, class_name)?;
+        Ok(self.ram[address])
+    }
+
+    fn write_segment(&mut self, segment: &str, index: u16, class_name: &str, value: i16) -> Result<(), VmError> {
+        if segment == "constant" {
+            return Err(VmError::PopToConstant { file: String::from(class_name) });
+        }
+        let address = self.address(segment, index, class_name)?;
+        self.ram[address] = value;
+        Ok(())
+    }
+
+    fn static_address(&mut self, class_name: &str, index: u16) -> Result<u16, VmError> {
+        //Allocated in order of first use, like the writer does
+        let symbol = format!("{}.{}", class_name, index);
+        if let Some(Address::Absolute(addr)) = self.symbol_table.get_address(&symbol) {
+            return Ok(*addr);
+        }
+        self.symbol_table.allocate_static(&symbol)
+    }
+
+    fn push(&mut self, value: i16) -> Result<(), VmError> {
+        let sp = self.ram[SP] as usize;
+        if sp < STACK_BASE || sp >= self.stack_top {
+            return Err(self.stack_error("Stack overflow", sp));
+        }
+        self.ram[sp] = value;
+        self.ram[SP] += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<i16, VmError> {
+        let sp = self.ram[SP] as usize;
+        if sp <= STACK_BASE {
+            return Err(self.stack_error("Stack underflow", sp));
+        }
+        if sp > self.stack_top {
+            return Err(self.stack_error("Stack overflow", sp));
+        }
+        self.ram[SP] -= 1;
+        Ok(self.ram[sp - 1])
+    }
+
+    fn stack_error(&self, message: &str, sp: usize) -> VmError {
+        self.runtime_error(format!("{} (SP = {})", message, sp))
+    }
+
+    //A pointer plus an offset, checked to land in RAM so a bad pointer is an error
+    //rather than a panic
+    fn ram_address(&self, base: i16, offset: isize) -> Result<usize, VmError> {
+        let address = base as isize + offset;
+        if address < 0 || address as usize >= self.ram.len() {
+            return Err(self.runtime_error(format!("Address {} is outside RAM", address)));
+        }
+        Ok(address as usize)
+    }
+
+    //pc has already moved past the command being run
+    fn runtime_error(&self, message: String) -> VmError {
+        VmError::Runtime { index: self.pc.saturating_sub(1), message }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn push_constant(value: u16) -> Command {
+        Command::Push { segment: String::from("constant"), index: value, class_name: String::from("Main") }
+    }
+
+    #[test]
+    fn add_on_empty_stack() {
+        let mut vm = Vm::new(vec![push_constant(7), Command::Arithmetic(TokenType::Add)]).unwrap();
+        let err = vm.run().unwrap_err();
+        assert_eq!(err.to_string(), "Runtime error at command 1: Stack underflow (SP = 256)");
+        assert_eq!(vm.sp(), 256);
+    }
+
+    #[test]
+    fn push_past_stack_top() {
+        let mut vm = Vm::new(vec![push_constant(1), push_constant(2), push_constant(3)]).unwrap();
+        vm.set_stack_top(258);
+        let err = vm.run().unwrap_err();
+        assert_eq!(err.to_string(), "Runtime error at command 2: Stack overflow (SP = 258)");
+        assert_eq!(vm.stack(), &[1, 2]);
+    }
+
+    #[test]
+    fn return_without_call_halts() {
+        let mut vm = Vm::new(vec![
+            Command::Function { symbol: String::from("Main.main"), nvars: 0 },
+            push_constant(1),
+            Command::Return,
+        ])
+        .unwrap();
+        vm.run().unwrap();
+        assert!(vm.halted());
+        assert_eq!(vm.stack(), &[1]);
+    }
+
+    #[test]
+    fn tail_call_without_call_halts_after_callee() {
+        let mut vm = Vm::new(vec![
+            Command::Function { symbol: String::from("Main.main"), nvars: 0 },
+            push_constant(4),
+            Command::TailCall { symbol: String::from("Main.double"), nargs: 1 },
+            Command::Function { symbol: String::from("Main.double"), nvars: 0 },
+            Command::Push { segment: String::from("argument"), index: 0, class_name: String::from("Main") },
+            Command::Push { segment: String::from("argument"), index: 0, class_name: String::from("Main") },
+            Command::Arithmetic(TokenType::Add),
+            Command::Return,
+        ])
+        .unwrap();
+        vm.run().unwrap();
+        assert!(vm.halted());
+        assert_eq!(vm.stack(), &[8]);
+    }
+
+    #[test]
+    fn corrupt_frame_is_a_runtime_error() {
+        let mut vm = Vm::new(vec![
+            Command::Call { symbol: String::from("Main.f"), nargs: 0 },
+            Command::Function { symbol: String::from("Main.f"), nvars: 0 },
+            push_constant(1),
+            Command::Return,
+        ])
+        .unwrap();
+        vm.step().unwrap();
+        vm.step().unwrap();
+        vm.step().unwrap();
+        vm.ram_mut()[LCL] = 2;
+        let err = vm.step().unwrap_err();
+        assert_eq!(err.to_string(), "Runtime error at command 3: Address -2 is outside RAM");
+    }
+
+    #[test]
+    fn segment_addresses_outside_ram() {
+        let ram = Command::Push { segment: String::from("ram"), index: 40000, class_name: String::from("Main") };
+        let err = Vm::new(vec![ram]).unwrap().run().unwrap_err();
+        assert_eq!(err.to_string(), "Main: Index 40000 is out of range for the ram segment");
+
+        let local = Command::Push { segment: String::from("local"), index: 1, class_name: String::from("Main") };
+        let mut negative = Vm::new(vec![local]).unwrap();
+        negative.ram_mut()[LCL] = -5;
+        let err = negative.run().unwrap_err();
+        assert_eq!(err.to_string(), "Runtime error at command 0: Address -4 is outside RAM");
+
+        let that = Command::Pop { segment: String::from("that"), index: 2, class_name: String::from("Main") };
+        let mut past_end = Vm::new(vec![push_constant(1), that]).unwrap();
+        past_end.ram_mut()[THAT] = 32767;
+        let err = past_end.run().unwrap_err();
+        assert_eq!(err.to_string(), "Runtime error at command 1: Address 32769 is outside RAM");
+    }
+
+    #[test]
+    fn stack_top_stops_at_screen() {
+        let mut vm = Vm::new(vec![]).unwrap();
+        vm.set_stack_top(40000);
+        assert_eq!(vm.stack_top, 16384);
+    }
+
+    #[test]
+    fn add_constants() {
+        let mut vm = Vm::new(vec![push_constant(7), push_constant(8), Command::Arithmetic(TokenType::Add)]).unwrap();
+        vm.run().unwrap();
+        assert_eq!(vm.stack(), &[15]);
+        assert_eq!(vm.ram()[0], 257);
+    }
+
+    #[test]
+    fn comparisons_and_segments() {
+        let commands = vec![
+            push_constant(3),
+            push_constant(5),
+            Command::Arithmetic(TokenType::LessThan),
+            Command::Pop { segment: String::from("temp"), index: 2, class_name: String::from("Main") },
+            push_constant(9),
+            Command::Pop { segment: String::from("static"), index: 4, class_name: String::from("Main") },
+            Command::Push { segment: String::from("temp"), index: 2, class_name: String::from("Main") },
+            Command::Arithmetic(TokenType::Negate),
+        ];
+        let mut vm = Vm::new(commands).unwrap();
+        vm.run().unwrap();
+        assert_eq!(vm.ram()[7], -1);
+        assert_eq!(vm.ram()[16], 9);
+        assert_eq!(vm.stack(), &[1]);
+    }
+
+    #[test]
+    fn loop_with_if_goto() {
+        //Counts down from 3 at temp 0, adding each value into temp 1
+        let temp = |index| Command::Push { segment: String::from("temp"), index, class_name: String::new() };
+        let pop_temp = |index| Command::Pop { segment: String::from("temp"), index, class_name: String::new() };
+        let commands = vec![
+            push_constant(3),
+            pop_temp(0),
+            Command::Label(String::from("LOOP")),
+            temp(1),
+            temp(0),
+            Command::Arithmetic(TokenType::Add),
+            pop_temp(1),
+            temp(0),
+            push_constant(1),
+            Command::Arithmetic(TokenType::Subtract),
+            pop_temp(0),
+            temp(0),
+            Command::If(String::from("LOOP")),
+        ];
+        let mut vm = Vm::new(commands).unwrap();
+        vm.run().unwrap();
+        assert_eq!(vm.ram()[6], 6);
+        assert!(vm.stack().is_empty());
+    }
+
+    #[test]
+    fn call_and_return() {
+        let commands = vec![
+            Command::Function { symbol: String::from("Sys.init"), nvars: 0 },
+            push_constant(4),
+            push_constant(6),
+            Command::Call { symbol: String::from("Main.mult"), nargs: 2 },
+            Command::Pop { segment: String::from("temp"), index: 0, class_name: String::new() },
+            Command::Return,
+            //Main.mult(a, b) adds a to a local b times
+            Command::Function { symbol: String::from("Main.mult"), nvars: 1 },
+            Command::Label(String::from("LOOP")),
+            Command::Push { segment: String::from("argument"), index: 1, class_name: String::new() },
+            Command::Arithmetic(TokenType::Not),
+            Command::Arithmetic(TokenType::Not),
+            push_constant(0),
+            Command::Arithmetic(TokenType::Equal),
+            Command::If(String::from("END")),
+            Command::Push { segment: String::from("local"), index: 0, class_name: String::new() },
+            Command::Push { segment: String::from("argument"), index: 0, class_name: String::new() },
+            Command::Arithmetic(TokenType::Add),
+            Command::Pop { segment: String::from("local"), index: 0, class_name: String::new() },
+            Command::Push { segment: String::from("argument"), index: 1, class_name: String::new() },
+            push_constant(1),
+            Command::Arithmetic(TokenType::Subtract),
+            Command::Pop { segment: String::from("argument"), index: 1, class_name: String::new() },
+            Command::Goto(String::from("LOOP")),
+            Command::Label(String::from("END")),
+            Command::Push { segment: String::from("local"), index: 0, class_name: String::new() },
+            Command::Return,
+        ];
+        let mut vm = Vm::new(commands).unwrap();
+        vm.bootstrap().unwrap();
+        vm.run().unwrap();
+        assert_eq!(vm.ram()[5], 24);
+    }
+
+    #[test]
+    fn step_through_arithmetic() {
+        let commands = vec![
+            push_constant(7),
+            push_constant(8),
+            Command::Arithmetic(TokenType::Add),
+            push_constant(5),
+            Command::Arithmetic(TokenType::Subtract),
+        ];
+        let mut vm = Vm::new(commands).unwrap();
+        let mut trace: Vec<(i16, Vec<i16>)> = vec![];
+        while !vm.halted() {
+            vm.step().unwrap();
+            trace.push((vm.sp(), vm.top(2).to_vec()));
+        }
+        assert_eq!(trace, vec![
+            (257, vec![7]),
+            (258, vec![7, 8]),
+            (257, vec![15]),
+            (258, vec![15, 5]),
+            (257, vec![10]),
+        ]);
+        assert_eq!(vm.pc(), 5);
+        assert!(vm.current_command().is_none());
+        assert_eq!(vm.segment_bases(), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let undefined = Vm::new(vec![Command::Goto(String::from("NOWHERE"))]);
+        assert!(matches!(undefined, Err(VmError::UndefinedLabel { .. })));
+
+        let mut forever = Vm::new(vec![Command::Label(String::from("L")), Command::Goto(String::from("L"))]).unwrap();
+        forever.set_step_limit(100);
+        assert!(matches!(forever.run(), Err(VmError::Runtime { .. })));
+    }
+
+    //Main.sum(n, acc) adds n..1 to acc, recursing in tail position. Main.start has no
+    //arguments of its own, so its tail call has to grow the argument block
+    fn tail_recursive_sum() -> Vec<Command> {
+        let argument = |index| Command::Push { segment: String::from("argument"), index, class_name: String::new() };
+        vec![
+            Command::Function { symbol: String::from("Sys.init"), nvars: 0 },
+            Command::Call { symbol: String::from("Main.start"), nargs: 0 },
+            Command::Pop { segment: String::from("temp"), index: 0, class_name: String::new() },
+            Command::Return,
+            Command::Function { symbol: String::from("Main.start"), nvars: 0 },
+            push_constant(10),
+            push_constant(0),
+            Command::Call { symbol: String::from("Main.sum"), nargs: 2 },
+            Command::Return,
+            Command::Function { symbol: String::from("Main.sum"), nvars: 0 },
+            argument(0),
+            push_constant(0),
+            Command::Arithmetic(TokenType::Equal),
+            Command::If(String::from("BASE")),
+            argument(0),
+            push_constant(1),
+            Command::Arithmetic(TokenType::Subtract),
+            argument(1),
+            argument(0),
+            Command::Arithmetic(TokenType::Add),
+            Command::Call { symbol: String::from("Main.sum"), nargs: 2 },
+            Command::Return,
+            Command::Label(String::from("BASE")),
+            argument(1),
+            Command::Return,
+        ]
+    }
+
+    #[test]
+    fn tail_call_reuses_frame() {
+        let optimized = ::optimizer::optimize(tail_recursive_sum());
+        assert_eq!(optimized.iter().filter(|c| matches!(c, Command::TailCall { .. })).count(), 2);
+
+        let mut results = vec![];
+        for commands in [tail_recursive_sum(), optimized] {
+            let mut vm = Vm::new(commands).unwrap();
+            vm.bootstrap().unwrap();
+            let mut deepest = 0;
+            while !vm.halted() {
+                vm.step().unwrap();
+                deepest = deepest.max(vm.sp());
+            }
+            results.push((vm.ram()[5], deepest));
+        }
+        assert_eq!(results[0].0, 55);
+        assert_eq!(results[1].0, 55);
+        //Ten nested frames without the optimization, one reused frame with it
+        assert!(results[1].1 < results[0].1 - 50);
+    }
+}
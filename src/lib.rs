@@ -0,0 +1,22 @@
+extern crate regex;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+
+pub mod parser;
+pub mod writer;
+pub mod tokenizer;
+pub mod symbol_table;
+pub mod vm;
+pub mod error;
+pub mod assembler;
+pub mod optimizer;
+pub mod validator;
+pub mod interpreter;
+#[cfg(test)]
+mod roundtrip;
@@ -1,5 +0,0 @@
-pub mod parser;
-pub mod writer;
-pub mod tokenizer;
-pub mod symbol_table;
-pub mod vm;
\ No newline at end of file
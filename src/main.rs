@@ -1,13 +1,15 @@
-extern crate regex;
+extern crate rusthackvm;
 
+use rusthackvm::vm;
 use std::env;
 use std::process;
-use lib::vm;
-
-mod lib;
 
 fn main() {
     let config = vm::Config::new(env::args()).unwrap_or_else(|err| {
+        if let Some(exit) = err.downcast_ref::<vm::EarlyExit>() {
+            println!("{}", exit);
+            process::exit(0);
+        }
         eprintln!("Could not parse file {}", err);
         process::exit(1);
     });
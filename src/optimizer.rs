@@ -0,0 +1,383 @@
+use parser::Command;
+use std::collections::HashSet;
+use tokenizer::TokenType;
+use validator::scoped_label;
+
+pub fn optimize(commands: Vec<Command>) -> Vec<Command> {
+    strip_unreferenced_labels(tail_calls(drop_jumps_to_next(peephole(fold_constants(commands)))))
+}
+
+fn peephole(commands: Vec<Command>) -> Vec<Command> {
+    //Each command is compared against the last one kept, so a fused result can itself be
+    //fused with whatever comes next
+    let mut out: Vec<Command> = Vec::with_capacity(commands.len());
+    for command in commands {
+        let fused = match out.last() {
+            Some(previous) => fuse(previous, &command),
+            None => None,
+        };
+        match fused {
+            Some(replacement) => {
+                out.pop();
+                out.extend(replacement);
+            }
+            None => out.push(command),
+        }
+    }
+    out
+}
+
+fn drop_jumps_to_next(commands: Vec<Command>) -> Vec<Command> {
+    //goto L directly followed by label L is a jump to the next instruction. The label is
+    //left for strip_unreferenced_labels, since something else may still jump to it
+    let mut out: Vec<Command> = Vec::with_capacity(commands.len());
+    let mut iter = commands.into_iter().peekable();
+    while let Some(command) = iter.next() {
+        if let (Command::Goto(target), Some(Command::Label(label))) = (&command, iter.peek()) {
+            if target == label {
+                continue;
+            }
+        }
+        out.push(command);
+    }
+    out
+}
+
+fn tail_calls(commands: Vec<Command>) -> Vec<Command> {
+    //call G n directly followed by return can reuse the current frame. Only done inside a
+    //function, since code before the first function has no frame to reuse
+    let mut out: Vec<Command> = Vec::with_capacity(commands.len());
+    let mut in_function = false;
+    for command in commands {
+        match (out.last(), &command) {
+            (Some(Command::Call { symbol, nargs }), Command::Return) if in_function => {
+                let tail_call = Command::TailCall { symbol: symbol.clone(), nargs: *nargs };
+                out.pop();
+                out.push(tail_call);
+                continue;
+            }
+            (_, Command::Function { .. }) => in_function = true,
+            _ => {}
+        }
+        out.push(command);
+    }
+    out
+}
+
+fn strip_unreferenced_labels(commands: Vec<Command>) -> Vec<Command> {
+    //A label no goto or if-goto targets is only an unused assembler symbol. Function entry
+    //points are Function commands, not labels, so calls never depend on this
+    let referenced = referenced_labels(&commands);
+    let mut function: Option<String> = None;
+    commands
+        .into_iter()
+        .filter(|command| match command {
+            Command::Function { symbol, .. } => {
+                function = Some(symbol.clone());
+                true
+            }
+            Command::Label(label) => referenced.contains(&scoped_label(function.as_deref(), label)),
+            _ => true,
+        })
+        .collect()
+}
+
+fn referenced_labels(commands: &[Command]) -> HashSet<String> {
+    //Every label a goto or if-goto can reach, under its function scoped name
+    let mut referenced: HashSet<String> = HashSet::new();
+    let mut function: Option<&str> = None;
+    for command in commands {
+        match command {
+            Command::Function { symbol, .. } => function = Some(symbol),
+            Command::Goto(label) | Command::If(label) => {
+                referenced.insert(scoped_label(function, label));
+            }
+            _ => {}
+        }
+    }
+    referenced
+}
+
+fn fold_constants(commands: Vec<Command>) -> Vec<Command> {
+    //Arithmetic on constants already pushed is computed now and replaced with its result.
+    //The output doubles as a stack, so folded results feed into later folds.
+    let mut out: Vec<Command> = Vec::with_capacity(commands.len());
+    for command in commands {
+        if let Command::Arithmetic(op) = command {
+            let len = out.len();
+            let folded = match op {
+                TokenType::Negate | TokenType::Not | TokenType::ShiftLeft | TokenType::ShiftRight if len >= 1 => constant_value(&out[len - 1])
+                    .and_then(|x| fold_unary(op, x))
+                    .map(|value| (1, constant_command(value))),
+                TokenType::Negate | TokenType::Not | TokenType::ShiftLeft | TokenType::ShiftRight => None,
+                _ if len >= 2 => {
+                    match (constant_value(&out[len - 2]), constant_value(&out[len - 1])) {
+                        (Some(x), Some(y)) => fold_binary(op, x, y)
+                            .map(|value| (2, constant_command(value))),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            match folded {
+                Some((consumed, replacement)) => {
+                    out.truncate(len - consumed);
+                    out.push(replacement);
+                }
+                None => out.push(command),
+            }
+        } else {
+            out.push(command);
+        }
+    }
+    out
+}
+
+fn constant_value(command: &Command) -> Option<i16> {
+    match command {
+        Command::Push { segment, index, .. } if segment == "constant" => Some(*index as i16),
+        _ => None,
+    }
+}
+
+fn fold_unary(op: TokenType, x: i16) -> Option<i16> {
+    match op {
+        TokenType::Negate => Some(x.wrapping_neg()),
+        TokenType::Not => Some(!x),
+        TokenType::ShiftLeft => Some(x.wrapping_shl(1)),
+        TokenType::ShiftRight => Some(((x as u16) >> 1) as i16),
+        _ => None,
+    }
+}
+
+fn fold_binary(op: TokenType, x: i16, y: i16) -> Option<i16> {
+    //Values wrap like the 16-bit Hack ALU, and comparisons give -1 for true and 0 for false
+    let truth = |b: bool| if b { -1 } else { 0 };
+    match op {
+        TokenType::Add => Some(x.wrapping_add(y)),
+        TokenType::Subtract => Some(x.wrapping_sub(y)),
+        TokenType::And => Some(x & y),
+        TokenType::Or => Some(x | y),
+        TokenType::Equal => Some(truth(x == y)),
+        TokenType::GreaterThan => Some(truth(x > y)),
+        TokenType::LessThan => Some(truth(x < y)),
+        _ => None,
+    }
+}
+
+fn constant_command(value: i16) -> Command {
+    Command::Push {
+        segment: String::from("constant"),
+        index: value as u16,
+        class_name: String::new(),
+    }
+}
+
+fn fuse(previous: &Command, next: &Command) -> Option<Vec<Command>> {
+    match (previous, next) {
+        //push X / pop X leaves both the stack and X unchanged
+        (
+            Command::Push {
+                segment: push_segment,
+                index: push_index,
+                class_name: push_class,
+            },
+            Command::Pop {
+                segment,
+                index,
+                class_name,
+            },
+        ) if push_segment == segment
+            && push_index == index
+            && push_class == class_name
+            && segment != "constant" =>
+        {
+            Some(vec![])
+        }
+        //push constant k / pop segment i stores k directly without touching the stack
+        (
+            Command::Push {
+                segment: push_segment,
+                index: value,
+                ..
+            },
+            Command::Pop {
+                segment,
+                index,
+                class_name,
+            },
+        ) if push_segment == "constant" => Some(vec![Command::Store {
+            segment: segment.clone(),
+            index: *index,
+            value: *value,
+            class_name: class_name.clone(),
+        }]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn push(segment: &str, index: u16) -> Command {
+        Command::Push {
+            segment: String::from(segment),
+            index,
+            class_name: String::from("Main"),
+        }
+    }
+
+    fn pop(segment: &str, index: u16) -> Command {
+        Command::Pop {
+            segment: String::from(segment),
+            index,
+            class_name: String::from("Main"),
+        }
+    }
+
+    fn constant(value: u16) -> Command {
+        Command::Push {
+            segment: String::from("constant"),
+            index: value,
+            class_name: String::new(),
+        }
+    }
+
+    fn arithmetic(op: TokenType) -> Command {
+        Command::Arithmetic(op)
+    }
+
+    #[test]
+    fn fold_add() {
+        let before = vec![constant(2), constant(3), arithmetic(TokenType::Add)];
+        assert_eq!(optimize(before), vec![constant(5)]);
+    }
+
+    #[test]
+    fn fold_chained() {
+        let before = vec![
+            constant(2),
+            constant(3),
+            arithmetic(TokenType::Add),
+            constant(4),
+            arithmetic(TokenType::Subtract),
+            constant(6),
+            arithmetic(TokenType::Or),
+        ];
+        assert_eq!(optimize(before), vec![constant(7)]);
+    }
+
+    #[test]
+    fn fold_negative_and_comparisons() {
+        let sub = vec![constant(2), constant(3), arithmetic(TokenType::Subtract)];
+        let gt = vec![constant(7), constant(3), arithmetic(TokenType::GreaterThan)];
+        let eq = vec![constant(7), constant(3), arithmetic(TokenType::Equal)];
+        assert_eq!(optimize(sub), vec![constant(-1i16 as u16)]);
+        assert_eq!(optimize(gt), vec![constant(-1i16 as u16)]);
+        assert_eq!(optimize(eq), vec![constant(0)]);
+    }
+
+    #[test]
+    fn fold_wraps_to_16_bits() {
+        let before = vec![constant(32767), constant(1), arithmetic(TokenType::Add)];
+        assert_eq!(optimize(before), vec![constant(-32768i16 as u16)]);
+        let not = vec![constant(0), arithmetic(TokenType::Not)];
+        assert_eq!(optimize(not), vec![constant(-1i16 as u16)]);
+    }
+
+    #[test]
+    fn fold_ignores_non_constants() {
+        let before = vec![push("local", 0), constant(3), arithmetic(TokenType::Add)];
+        assert_eq!(optimize(before.clone()), before);
+    }
+
+    #[test]
+    fn push_pop_same_location() {
+        let before = vec![push("local", 1), pop("local", 1), push("local", 1), pop("local", 2)];
+        assert_eq!(optimize(before), vec![push("local", 1), pop("local", 2)]);
+    }
+
+    #[test]
+    fn push_constant_pop_fuses_to_store() {
+        let before = vec![push("constant", 10), pop("local", 0), push("local", 0)];
+        let after = vec![
+            Command::Store {
+                segment: String::from("local"),
+                index: 0,
+                value: 10,
+                class_name: String::from("Main"),
+            },
+            push("local", 0),
+        ];
+        assert_eq!(optimize(before), after);
+    }
+
+    #[test]
+    fn goto_next_label_removed() {
+        let before = vec![
+            Command::Goto(String::from("SKIP")),
+            Command::Label(String::from("SKIP")),
+            Command::Goto(String::from("OTHER")),
+            Command::Label(String::from("SKIP2")),
+        ];
+        assert_eq!(optimize(before), vec![Command::Goto(String::from("OTHER"))]);
+    }
+
+    #[test]
+    fn unreferenced_label_removed() {
+        let before = vec![
+            Command::Function { symbol: String::from("Main.main"), nvars: 0 },
+            Command::Label(String::from("DEAD")),
+            Command::Label(String::from("LOOP")),
+            Command::Goto(String::from("LOOP")),
+        ];
+        let after = vec![
+            Command::Function { symbol: String::from("Main.main"), nvars: 0 },
+            Command::Label(String::from("LOOP")),
+            Command::Goto(String::from("LOOP")),
+        ];
+        assert_eq!(optimize(before), after);
+    }
+
+    #[test]
+    fn goto_next_label_keeps_referenced_label() {
+        let function = |symbol: &str| Command::Function { symbol: String::from(symbol), nvars: 0 };
+        let before = vec![
+            function("Main.main"),
+            Command::Label(String::from("LOOP")),
+            Command::Goto(String::from("SKIP")),
+            Command::Label(String::from("SKIP")),
+            Command::If(String::from("SKIP")),
+            function("Main.other"),
+            Command::Goto(String::from("LOOP")),
+            Command::Label(String::from("LOOP")),
+        ];
+        let after = vec![
+            function("Main.main"),
+            Command::Label(String::from("SKIP")),
+            Command::If(String::from("SKIP")),
+            function("Main.other"),
+        ];
+        assert_eq!(optimize(before), after);
+    }
+
+    #[test]
+    fn call_then_return_becomes_tail_call() {
+        let call = || Command::Call { symbol: String::from("Main.sum"), nargs: 2 };
+        let before = vec![
+            call(),
+            Command::Return,
+            Command::Function { symbol: String::from("Main.sum"), nvars: 0 },
+            call(),
+            Command::Return,
+        ];
+        let after = vec![
+            call(),
+            Command::Return,
+            Command::Function { symbol: String::from("Main.sum"), nvars: 0 },
+            Command::TailCall { symbol: String::from("Main.sum"), nargs: 2 },
+        ];
+        assert_eq!(optimize(before), after);
+    }
+}
@@ -0,0 +1,908 @@
+use error::VmError;
+use tokenizer::{default_ruleset, BlockComments, Token, TokenList, TokenType, Tokenizer};
+use std::borrow::Borrow;
+use std::fmt;
+use std::io::{BufRead, Lines};
+use std::str::FromStr;
+use std::slice::Iter;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Command {
+    //A constant's index holds its value as a 16-bit two's complement pattern, so negative
+    //constants come out of index as i16
+    Push { segment: String, index: u16, class_name: String },
+    Pop { segment: String, index: u16, class_name: String},
+    Arithmetic(TokenType),
+    Goto(String),
+    If(String),
+    Label(String),
+    Function { symbol: String, nvars: u16 },
+    Call { symbol: String, nargs: u16 },
+    Return,
+    //Never parsed from source. The optimizer fuses push constant + pop into a Store
+    Store { segment: String, index: u16, value: u16, class_name: String },
+    //Never parsed from source. The optimizer turns call + return into a call that reuses the frame
+    TailCall { symbol: String, nargs: u16 },
+}
+
+//Renders commands back in VM syntax. The optimizer's fused commands come out as the lines
+//they replaced, one per line
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Push { segment, index, .. } if segment == "constant" => {
+                write!(f, "push constant {}", *index as i16)
+            }
+            Command::Push { segment, index, .. } => write!(f, "push {} {}", segment, index),
+            Command::Pop { segment, index, .. } => write!(f, "pop {} {}", segment, index),
+            Command::Arithmetic(op) => {
+                let keyword = match op {
+                    TokenType::Add => "add",
+                    TokenType::Subtract => "sub",
+                    TokenType::Negate => "neg",
+                    TokenType::Equal => "eq",
+                    TokenType::GreaterThan => "gt",
+                    TokenType::LessThan => "lt",
+                    TokenType::And => "and",
+                    TokenType::Or => "or",
+                    TokenType::Not => "not",
+                    TokenType::ShiftLeft => "shiftleft",
+                    TokenType::ShiftRight => "shiftright",
+                    other => return write!(f, "{:?}", other),
+                };
+                write!(f, "{}", keyword)
+            }
+            Command::Goto(label) => write!(f, "goto {}", label),
+            Command::If(label) => write!(f, "if-goto {}", label),
+            Command::Label(label) => write!(f, "label {}", label),
+            Command::Function { symbol, nvars } => write!(f, "function {} {}", symbol, nvars),
+            Command::Call { symbol, nargs } => write!(f, "call {} {}", symbol, nargs),
+            Command::Return => write!(f, "return"),
+            Command::Store { segment, index, value, .. } => {
+                write!(f, "push constant {}\npop {} {}", *value as i16, segment, index)
+            }
+            Command::TailCall { symbol, nargs } => write!(f, "call {} {}\nreturn", symbol, nargs),
+        }
+    }
+}
+
+thread_local! {
+    //Compiled once per thread, so parsing line after line doesn't rebuild every regex
+    static DEFAULT_TOKENIZER: Tokenizer = Tokenizer::from(default_ruleset());
+}
+
+//Parses a single line of VM code. Push and pop get an empty class name, and a line with no
+//command on it (blank or only a comment) is a Keyword error
+impl FromStr for Command {
+    type Err = VmError;
+
+    fn from_str(line: &str) -> Result<Command, VmError> {
+        let tokens = DEFAULT_TOKENIZER.with(|tokenizer| tokenizer.tokenize(line)).map_err(|err| err.located("", 1))?;
+        match Parser::from(vec![tokens], String::new()).advance()? {
+            Some(command) => Ok(command),
+            None => Err(VmError::Keyword { file: String::new(), line: 1 }),
+        }
+    }
+}
+
+//A command along with the VM source line it was parsed from
+#[derive(Debug, PartialEq)]
+pub struct SourceCommand {
+    pub command: Command,
+    pub source: String,
+    pub line: usize,
+}
+
+#[derive(Debug)]
+pub struct Parser {
+    tokens: Vec<TokenList>,
+    //tokens holds one list per source line, blank and comment lines included, so progress
+    //is counted in lines. command_count only counts lines that produced a command
+    next_line: usize,
+    total_lines: usize,
+    command_count: usize,
+    class_name: String,
+    source: String,
+    line: usize,
+    max_count: u16,
+}
+
+impl Parser {
+    //No real function needs more locals or arguments than this, so a bigger count is
+    //most likely a typo that would otherwise write thousands of pushes
+    pub const DEFAULT_MAX_COUNT: u16 = 32;
+
+    pub fn new() -> Parser {
+        Parser {
+            tokens: vec![],
+            next_line: 0,
+            total_lines: 0,
+            command_count: 0,
+            class_name: String::new(),
+            source: String::new(),
+            line: 0,
+            max_count: Parser::DEFAULT_MAX_COUNT,
+        }
+    }
+
+    pub fn from(tokens: Vec<TokenList>, class_name: String) -> Parser {
+        let l = tokens.len();
+        Parser {
+            tokens,
+            next_line: 0,
+            total_lines: l,
+            command_count: 0,
+            class_name,
+            source: String::new(),
+            line: 0,
+            max_count: Parser::DEFAULT_MAX_COUNT,
+        }
+    }
+
+    //Highest nvars or nargs a function or call may use
+    pub fn set_max_count(&mut self, max_count: u16) {
+        self.max_count = max_count;
+    }
+
+    pub fn has_more_commands(&self) -> bool {
+        self.next_line < self.total_lines
+    }
+
+    pub fn advance(&mut self) -> Result<Option<Command>, VmError> {
+        let index = self.next_line;
+        //Lines are numbered from 1, so this is the index of the line being parsed plus one
+        self.line = self.next_line + 1;
+        self.next_line += 1;
+        self.source = Parser::source_text(&self.tokens[index]);
+        let command = self.parse(&self.tokens[index])?;
+        if command.is_some() {
+            self.command_count += 1;
+        }
+        Ok(command)
+    }
+
+    //Parses the next line of a file that is being read one line at a time, in place of the
+    //token lists given to Parser::from. line and source refer to it afterwards
+    pub fn parse_line(&mut self, tokens: &[Token]) -> Result<Option<Command>, VmError> {
+        self.line += 1;
+        self.source = Parser::source_text(tokens);
+        let command = self.parse(tokens)?;
+        if command.is_some() {
+            self.command_count += 1;
+        }
+        Ok(command)
+    }
+
+    //Commands parsed so far. Blank and comment lines don't count
+    pub fn command_count(&self) -> usize {
+        self.command_count
+    }
+
+    //The source of the line most recently passed to advance, without any comment
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn class_name(&self) -> &str {
+        &self.class_name
+    }
+
+    //The line number of the command most recently passed to advance
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    fn source_text(token_list: &[Token]) -> String {
+        token_list
+            .iter()
+            .take_while(|t| t.token_type != TokenType::Comment)
+            .map(|t| t.token.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    fn parse(&self, token_list: &[Token]) -> Result<Option<Command>, VmError> {
+        let mut t_iter = token_list.iter();
+        //Empty lines or comments should return Ok(None), so the writer knows to skip them. Bad input or syntax should return an Error, so that we can interrupt parsing.
+        let mut result: Option<Command> = None;
+        //Need to handle empty lines
+        let c: &Token = match t_iter.next() {
+            Some(x) => x,
+            None => return Ok(result),
+        };
+
+        //Need to handle full line comments first.
+        if c.token_type == TokenType::Comment {
+            return Ok(result);
+        }
+
+        //First word should always be a keyword or command. Throw an error if not. The token type
+        //is checked too, so a rule that wrongly flags a symbol as a keyword can't let it through
+        let not_keyword = matches!(c.token_type, TokenType::Symbol | TokenType::Undefined);
+        if !c.is_keyword || not_keyword {
+            return Err(VmError::Keyword {
+                file: self.class_name.clone(),
+                line: self.line,
+            });
+        };
+
+        //Now we can start parsing the tokens. Use the first token to identify the command type, and route accordingly
+        result = match c.token_type {
+            TokenType::Pop | TokenType::Push => {
+                let arg1 = self.next_argument(&mut t_iter, "Memory Access")?;
+                let arg2 = self.next_argument(&mut t_iter, "Memory Access")?;
+                match self.mem_access_parse(c, arg1, arg2, self.class_name.clone())? {
+                    Some(comm) => Some(comm),
+                    None => {
+                        return Err(VmError::Argument {
+                            command: String::from("Memory Access"),
+                            file: self.class_name.clone(),
+                            line: self.line,
+                        })
+                    }
+                }
+            }
+
+            TokenType::Label | TokenType::If | TokenType::Goto => {
+                let arg1 = self.next_argument(&mut t_iter, "Control Flow")?;
+                match Parser::control_flow_parse(c, arg1) {
+                    Some(comm) => Some(comm),
+                    None => {
+                        return Err(VmError::Argument {
+                            command: String::from("Control Flow"),
+                            file: self.class_name.clone(),
+                            line: self.line,
+                        })
+                    }
+                }
+            }
+            // At this stage, any remaining commands should be Arithmetic
+            TokenType::Call | TokenType::Function => {
+                let arg1 = self.next_argument(&mut t_iter, "Function")?;
+                let arg2 = self.next_argument(&mut t_iter, "Function")?;
+                match self.function_command_parse(c, arg1, arg2)? {
+                    Some(comm) => Some(comm),
+                    None => {
+                        return Err(VmError::Argument {
+                            command: String::from("Function"),
+                            file: self.class_name.clone(),
+                            line: self.line,
+                        })
+                    }
+                }
+            }
+
+            TokenType::Return => Some(Command::Return),
+
+            _ => match Parser::arithmetic_parse(c) {
+                Some(comm) => Some(comm),
+                None => {
+                    return Err(VmError::Argument {
+                        command: String::from("Arithmetic"),
+                        file: self.class_name.clone(),
+                        line: self.line,
+                    })
+                }
+            },
+        };
+        Ok(result)
+    }
+
+    fn next_argument<'a>(&self, t_iter: &mut Iter<'a, Token>, command_type: &str) -> Result<&'a Token, VmError> {
+        //Missing arguments are reported as an ArgumentError instead of panicking
+        match t_iter.next() {
+            Some(token) => Ok(token),
+            None => Err(VmError::Argument {
+                command: String::from(command_type),
+                file: self.class_name.clone(),
+                line: self.line,
+            }),
+        }
+    }
+
+    fn mem_access_parse(
+        &self,
+        c: &Token,
+        arg1: &Token,
+        arg2: &Token,
+        class_name: String,
+    ) -> Result<Option<Command>, VmError> {
+        if arg1.token_type == TokenType::Symbol && arg2.token_type == TokenType::Index {
+            Ok(match c.token_type {
+                TokenType::Push => Some(Command::Push {
+                    segment: String::from(arg1.token.clone()),
+                    index: self.push_value(arg1, arg2)?,
+                    class_name
+                }),
+                //constant isn't in memory, so there is nowhere to pop to
+                TokenType::Pop if arg1.token == "constant" => None,
+                TokenType::Pop => Some(Command::Pop {
+                    segment: String::from(arg1.token.clone()),
+                    index: self.index_value(arg2)?,
+                    class_name
+                }),
+                _ => None,
+            })
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn control_flow_parse(c: &Token, arg1: &Token) -> Option<Command> {
+        if arg1.token_type == TokenType::Symbol {
+            match c.token_type {
+                TokenType::Label => Some(Command::Label(arg1.token.clone())),
+                TokenType::Goto => Some(Command::Goto(arg1.token.clone())),
+                TokenType::If => Some(Command::If(arg1.token.clone())),
+                _ => None,
+            }
+        } else {
+            None
+        }
+    }
+
+    fn function_command_parse(&self, c: &Token, arg1: &Token, arg2: &Token) -> Result<Option<Command>, VmError> {
+        if arg1.token_type == TokenType::Symbol && arg2.token_type == TokenType::Index {
+            Ok(match c.token_type {
+                TokenType::Function => Some(Command::Function {
+                    symbol: arg1.token.clone(),
+                    nvars: self.count_value(arg2)?,
+                }),
+                TokenType::Call => Some(Command::Call {
+                    symbol: arg1.token.clone(),
+                    nargs: self.count_value(arg2)?,
+                }),
+                _ => None,
+            })
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn push_value(&self, segment: &Token, arg: &Token) -> Result<u16, VmError> {
+        //Constants are 16-bit signed values, from -32768 to 32767
+        if segment.token == "constant" && arg.token.starts_with('-') {
+            return match arg.token.parse::<i16>() {
+                Ok(value) => Ok(value as u16),
+                Err(_) => Err(VmError::IndexOverflow {
+                    value: arg.token.clone(),
+                    file: self.class_name.clone(),
+                    line: self.line,
+                }),
+            };
+        }
+        let value = self.index_value(arg)?;
+        if segment.token == "constant" && value > 32767 {
+            return Err(VmError::ConstantOutOfRange {
+                value,
+                file: self.class_name.clone(),
+                line: self.line,
+            });
+        }
+        Ok(value)
+    }
+
+    fn count_value(&self, arg: &Token) -> Result<u16, VmError> {
+        let value = self.index_value(arg)?;
+        if value > self.max_count {
+            return Err(VmError::CountOutOfRange {
+                value,
+                limit: self.max_count,
+                file: self.class_name.clone(),
+                line: self.line,
+            });
+        }
+        Ok(value)
+    }
+
+    fn index_value(&self, arg: &Token) -> Result<u16, VmError> {
+        //The Index rule guarantees digits and an optional sign, but not that they fit in a u16
+        if arg.token.starts_with('-') {
+            return Err(VmError::NegativeIndex {
+                value: arg.token.clone(),
+                file: self.class_name.clone(),
+                line: self.line,
+            });
+        }
+        arg.token.parse::<u16>().map_err(|_| VmError::IndexOverflow {
+            value: arg.token.clone(),
+            file: self.class_name.clone(),
+            line: self.line,
+        })
+    }
+
+    fn arithmetic_parse(c: &Token) -> Option<Command> {
+        //Only reached by keywords no other branch took, which a custom rule can make anything
+        match c.token_type {
+            TokenType::Add
+            | TokenType::Subtract
+            | TokenType::Negate
+            | TokenType::Equal
+            | TokenType::LessThan
+            | TokenType::GreaterThan
+            | TokenType::And
+            | TokenType::Or
+            | TokenType::Not
+            | TokenType::ShiftLeft
+            | TokenType::ShiftRight => Some(Command::Arithmetic(c.token_type)),
+            _ => None,
+        }
+    }
+
+    //Add another method for processing the leftover tokens, warn on syntax violations
+}
+
+impl Default for Parser {
+    fn default() -> Parser {
+        Parser::new()
+    }
+}
+
+//Parses a reader's commands one line at a time as they are iterated, so the source is
+//never held in full. The tokenizer can be owned or borrowed, so several readers can share
+//one set of compiled rules
+pub struct CommandReader<R: BufRead, T: Borrow<Tokenizer>> {
+    lines: Lines<R>,
+    tokenizer: T,
+    parser: Parser,
+    comments: BlockComments,
+}
+
+impl<R: BufRead, T: Borrow<Tokenizer>> CommandReader<R, T> {
+    pub fn new(reader: R, class_name: String, tokenizer: T) -> CommandReader<R, T> {
+        let comments = BlockComments::new(tokenizer.borrow().comment_leads());
+        CommandReader {
+            lines: reader.lines(),
+            tokenizer,
+            parser: Parser::from(vec![], class_name),
+            comments,
+        }
+    }
+
+    pub fn set_max_count(&mut self, max_count: u16) {
+        self.parser.set_max_count(max_count);
+    }
+}
+
+impl<R: BufRead, T: Borrow<Tokenizer>> Iterator for CommandReader<R, T> {
+    type Item = Result<SourceCommand, VmError>;
+
+    fn next(&mut self) -> Option<Result<SourceCommand, VmError>> {
+        //Blank and comment-only lines are skipped. An error is yielded in place of the
+        //line's command, and iterating further carries on from the next line
+        for line in self.lines.by_ref() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => return Some(Err(VmError::from(err))),
+            };
+            let tokens = match self.tokenizer.borrow().tokenize(&self.comments.strip(&line)) {
+                Ok(tokens) => tokens,
+                Err(err) => {
+                    //The line still counts, so the ones after it keep their numbers
+                    let _ = self.parser.parse_line(&[]);
+                    return Some(Err(err.located(self.parser.class_name(), self.parser.line())));
+                }
+            };
+            match self.parser.parse_line(&tokens) {
+                Ok(Some(command)) => {
+                    return Some(Ok(SourceCommand {
+                        command,
+                        source: String::from(self.parser.source()),
+                        line: self.parser.line(),
+                    }))
+                }
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        None
+    }
+}
+
+//The commands in reader, parsed with the default rules as they are read
+pub fn commands_from_reader<R: BufRead>(
+    reader: R,
+    class_name: String,
+) -> impl Iterator<Item = Result<Command, VmError>> {
+    CommandReader::new(reader, class_name, Tokenizer::from(default_ruleset()))
+        .map(|result| result.map(|source_command| source_command.command))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn memory_access_parse_test() {
+        let parser = Parser::new();
+        let input: TokenList = vec![
+            Token::from(String::from("push"), TokenType::Push, true),
+            Token::from(String::from("local"), TokenType::Symbol, false),
+            Token::from(String::from("0"), TokenType::Index, false),
+        ];
+
+        let output = parser.parse(&input);
+
+        assert_eq!(
+            output.unwrap(),
+            Some(Command::Push {
+                segment: String::from("local"),
+                index: 0,
+                class_name: String::new()
+            })
+        );
+    }
+
+    #[test]
+    fn arithmetic_parse_test() {
+        let parser = Parser::new();
+        let input: TokenList = vec![Token::from(String::from("add"), TokenType::Add, true)];
+
+        let output = parser.parse(&input);
+        assert_eq!(output.unwrap(), Some(Command::Arithmetic(TokenType::Add)));
+    }
+
+    #[test]
+    fn comment_parse_test() {
+        let parser = Parser::new();
+        let input: TokenList = vec![
+            Token::from(String::from("//"), TokenType::Comment, false),
+            Token::from(String::from("hello"), TokenType::Symbol, false),
+        ];
+
+        let output = parser.parse(&input);
+        assert_eq!(output.unwrap(), None);
+    }
+
+    #[test]
+    fn inline_comment_parse_test() {
+        let parser = Parser::new();
+        let input: TokenList = vec![
+            Token::from(String::from("add"), TokenType::Add, true),
+            Token::from(String::from("//"), TokenType::Comment, false),
+        ];
+
+        let output = parser.parse(&input);
+        assert_eq!(output.unwrap(), Some(Command::Arithmetic(TokenType::Add)));
+    }
+
+    #[test]
+    fn no_tokens_parse_test() {
+        let parser = Parser::new();
+        let input: TokenList = vec![];
+
+        let output = parser.parse(&input);
+        assert_eq!(output.unwrap(), None);
+    }
+
+    #[test]
+    fn source_text_test() {
+        let tokens: Vec<TokenList> = vec![vec![
+            Token::from(String::from("push"), TokenType::Push, true),
+            Token::from(String::from("constant"), TokenType::Symbol, false),
+            Token::from(String::from("7"), TokenType::Index, false),
+            Token::from(String::from("//seven"), TokenType::Comment, false),
+        ]];
+        let mut parser = Parser::from(tokens, String::from("Main"));
+        parser.advance().unwrap();
+        assert_eq!(parser.source(), "push constant 7");
+    }
+
+    #[test]
+    fn missing_arguments_parse_test() {
+        let parser = Parser::new();
+        let no_args: TokenList = vec![Token::from(String::from("push"), TokenType::Push, true)];
+        let one_arg: TokenList = vec![
+            Token::from(String::from("push"), TokenType::Push, true),
+            Token::from(String::from("local"), TokenType::Symbol, false),
+        ];
+
+        assert!(matches!(parser.parse(&no_args), Err(VmError::Argument { .. })));
+        assert!(matches!(parser.parse(&one_arg), Err(VmError::Argument { .. })));
+    }
+
+    #[test]
+    fn advance_borrows_tokens_test() {
+        let tokenizer = Tokenizer::from(default_ruleset());
+        let lines = ["push constant 1", "// comment", "pop local 0"];
+        let tokens: Vec<TokenList> = lines.iter().map(|line| tokenizer.tokenize(line).unwrap()).collect();
+        let mut parser = Parser::from(tokens, String::from("Main"));
+
+        let mut commands = vec![];
+        while parser.has_more_commands() {
+            commands.push(parser.advance().unwrap());
+        }
+        assert_eq!(commands.len(), 3);
+        assert_eq!(commands[1], None);
+        assert_eq!(commands[2].as_ref().unwrap().to_string(), "pop local 0");
+        assert_eq!(parser.source(), "pop local 0");
+    }
+
+    #[test]
+    fn commands_from_reader_test() {
+        let source = "// Adds two numbers\npush constant 7\n\n/* block\ncomment */ push constant 8\nadd\npop local 0\n";
+        let commands: Vec<Command> = commands_from_reader(source.as_bytes(), String::from("Main"))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                Command::Push { segment: String::from("constant"), index: 7, class_name: String::from("Main") },
+                Command::Push { segment: String::from("constant"), index: 8, class_name: String::from("Main") },
+                Command::Arithmetic(TokenType::Add),
+                Command::Pop { segment: String::from("local"), index: 0, class_name: String::from("Main") },
+            ]
+        );
+
+        let mut bad = commands_from_reader("push constant 1\npush nowhere\n".as_bytes(), String::from("Main"));
+        assert!(bad.next().unwrap().is_ok());
+        let err = bad.next().unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "Main: Improper arguments for Memory Access command at line 2");
+        assert!(bad.next().is_none());
+    }
+
+    #[test]
+    fn parse_line_counts_lines_test() {
+        let tokenizer = Tokenizer::from(default_ruleset());
+        let mut parser = Parser::from(vec![], String::from("Main"));
+        assert_eq!(parser.parse_line(&tokenizer.tokenize("// setup").unwrap()).unwrap(), None);
+        let command = parser.parse_line(&tokenizer.tokenize("push constant 3").unwrap()).unwrap();
+        assert_eq!(command.unwrap().to_string(), "push constant 3");
+        assert_eq!((parser.line(), parser.source(), parser.command_count()), (2, "push constant 3", 1));
+        assert!(matches!(parser.parse_line(&tokenizer.tokenize("pop").unwrap()), Err(VmError::Argument { line: 3, .. })));
+    }
+
+    #[test]
+    fn index_overflow_test() {
+        let tokenizer = Tokenizer::from(default_ruleset());
+        let tokens = vec![tokenizer.tokenize("push constant 70000").unwrap(), tokenizer.tokenize("call Main.f 99999").unwrap()];
+        let mut parser = Parser::from(tokens, String::from("Main"));
+
+        let push = parser.advance().unwrap_err();
+        assert!(matches!(push, VmError::IndexOverflow { ref value, line: 1, .. } if value == "70000"));
+        assert_eq!(push.to_string(), "Main: Index 70000 at line 1 is too large");
+        assert!(matches!(parser.advance(), Err(VmError::IndexOverflow { line: 2, .. })));
+    }
+
+    #[test]
+    fn constant_range_test() {
+        let tokenizer = Tokenizer::from(default_ruleset());
+        let lines = ["push constant 32767", "push constant 32768", "push local 40000"];
+        let tokens = lines.iter().map(|line| tokenizer.tokenize(line).unwrap()).collect();
+        let mut parser = Parser::from(tokens, String::from("Main"));
+
+        assert!(parser.advance().is_ok());
+        assert!(matches!(parser.advance(), Err(VmError::ConstantOutOfRange { value: 32768, line: 2, .. })));
+        assert!(parser.advance().is_ok());
+    }
+
+    #[test]
+    fn count_limit_test() {
+        let tokenizer = Tokenizer::from(default_ruleset());
+        let lines = ["function Main.main 60000", "call Main.f 32", "call Main.f 33", "function Main.big 40"];
+        let tokens = lines.iter().map(|line| tokenizer.tokenize(line).unwrap()).collect();
+        let mut parser = Parser::from(tokens, String::from("Main"));
+
+        let function = parser.advance().unwrap_err();
+        assert!(matches!(function, VmError::CountOutOfRange { value: 60000, limit: 32, line: 1, .. }));
+        assert_eq!(function.to_string(), "Main: Count 60000 at line 1 is above the limit of 32");
+        assert!(parser.advance().is_ok());
+        assert!(matches!(parser.advance(), Err(VmError::CountOutOfRange { value: 33, .. })));
+        parser.set_max_count(64);
+        assert!(parser.advance().is_ok());
+    }
+
+    #[test]
+    fn negative_constant_test() {
+        let tokenizer = Tokenizer::from(default_ruleset());
+        let lines = ["push constant -1", "push constant -32768", "push constant -32769", "pop local -1"];
+        let tokens = lines.iter().map(|line| tokenizer.tokenize(line).unwrap()).collect();
+        let mut parser = Parser::from(tokens, String::from("Main"));
+
+        let minus_one = parser.advance().unwrap().unwrap();
+        assert_eq!(minus_one, Command::Push { segment: String::from("constant"), index: 65535, class_name: String::from("Main") });
+        assert_eq!(minus_one.to_string(), "push constant -1");
+        let lowest = parser.advance().unwrap().unwrap();
+        assert_eq!(lowest.to_string(), "push constant -32768");
+        assert!(matches!(parser.advance(), Err(VmError::IndexOverflow { ref value, .. }) if value == "-32769"));
+        let pop = parser.advance().unwrap_err();
+        assert_eq!(pop.to_string(), "Main: Index -1 at line 4 can't be negative");
+    }
+
+    #[test]
+    fn command_count_skips_blank_lines_test() {
+        let tokenizer = Tokenizer::from(default_ruleset());
+        let lines = ["", "push constant 1", "// comment", "", "push constant 2", "add // sum", ""];
+        let tokens = lines.iter().map(|line| tokenizer.tokenize(line).unwrap()).collect();
+        let mut parser = Parser::from(tokens, String::from("Main"));
+
+        let mut lines_read = 0;
+        while parser.has_more_commands() {
+            parser.advance().unwrap();
+            lines_read += 1;
+        }
+        assert_eq!(lines_read, 7);
+        assert_eq!(parser.command_count(), 3);
+        assert_eq!(parser.line(), 7);
+        assert!(!Parser::new().has_more_commands());
+    }
+
+    #[test]
+    fn line_numbers_past_u16_test() {
+        let source = format!("{}add\n", "// generated\n".repeat(70_000));
+        let tokenizer = Tokenizer::from(default_ruleset());
+        let commands: Vec<SourceCommand> =
+            CommandReader::new(source.as_bytes(), String::from("Main"), tokenizer).collect::<Result<_, _>>().unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].line, 70_001);
+
+        let mut tokens: Vec<TokenList> = vec![vec![]; 70_000];
+        tokens.push(vec![Token::from(String::from("add"), TokenType::Add, true)]);
+        let mut parser = Parser::from(tokens, String::from("Main"));
+        let mut parsed = vec![];
+        while parser.has_more_commands() {
+            if let Some(command) = parser.advance().unwrap() {
+                parsed.push(command);
+            }
+        }
+        assert_eq!(parsed, vec![Command::Arithmetic(TokenType::Add)]);
+    }
+
+    #[test]
+    fn dotted_symbols_test() {
+        assert_eq!(
+            "call Math.multiply 2".parse::<Command>().unwrap(),
+            Command::Call { symbol: String::from("Math.multiply"), nargs: 2 }
+        );
+        assert_eq!(
+            "function Output.printInt 1".parse::<Command>().unwrap(),
+            Command::Function { symbol: String::from("Output.printInt"), nvars: 1 }
+        );
+    }
+
+    #[test]
+    fn malformed_index_test() {
+        let padded = "push local 007".parse::<Command>().unwrap();
+        assert_eq!(padded, Command::Push { segment: String::from("local"), index: 7, class_name: String::new() });
+        let negative = "push local -1".parse::<Command>().unwrap_err();
+        assert!(matches!(negative, VmError::NegativeIndex { ref value, .. } if value == "-1"));
+        let trailing = "push local 3x".parse::<Command>().unwrap_err();
+        assert_eq!(trailing.to_string(), ": Unrecognized token 3x at line 1");
+    }
+
+    #[test]
+    fn from_str_test() {
+        assert_eq!(
+            "push local 0".parse::<Command>().unwrap(),
+            Command::Push { segment: String::from("local"), index: 0, class_name: String::new() }
+        );
+        assert_eq!(
+            "pop that 5 // comment".parse::<Command>().unwrap(),
+            Command::Pop { segment: String::from("that"), index: 5, class_name: String::new() }
+        );
+        assert_eq!("add".parse::<Command>().unwrap(), Command::Arithmetic(TokenType::Add));
+        assert_eq!("goto LOOP".parse::<Command>().unwrap(), Command::Goto(String::from("LOOP")));
+        assert_eq!("if-goto END".parse::<Command>().unwrap(), Command::If(String::from("END")));
+        assert_eq!("label END".parse::<Command>().unwrap(), Command::Label(String::from("END")));
+        assert_eq!(
+            "function Main.main 2".parse::<Command>().unwrap(),
+            Command::Function { symbol: String::from("Main.main"), nvars: 2 }
+        );
+        assert_eq!(
+            "call Math.multiply 2".parse::<Command>().unwrap(),
+            Command::Call { symbol: String::from("Math.multiply"), nargs: 2 }
+        );
+        assert_eq!("return".parse::<Command>().unwrap(), Command::Return);
+        assert!(matches!("// nothing".parse::<Command>(), Err(VmError::Keyword { .. })));
+        assert!(matches!("push local".parse::<Command>(), Err(VmError::Argument { .. })));
+    }
+
+    #[test]
+    fn pop_constant_test() {
+        let tokenizer = Tokenizer::from(default_ruleset());
+        let tokens = vec![tokenizer.tokenize("push constant 0").unwrap(), tokenizer.tokenize("pop constant 0").unwrap()];
+        let mut parser = Parser::from(tokens, String::from("Main"));
+
+        assert!(parser.advance().is_ok());
+        let pop = parser.advance().unwrap_err();
+        assert!(matches!(pop, VmError::Argument { line: 2, .. }));
+        assert_eq!(pop.to_string(), "Main: Improper arguments for Memory Access command at line 2");
+    }
+
+    #[test]
+    fn first_line_error_test() {
+        let tokenizer = Tokenizer::from(default_ruleset());
+        let mut parser = Parser::from(vec![tokenizer.tokenize("push local").unwrap()], String::from("Main"));
+        assert!(matches!(parser.advance(), Err(VmError::Argument { line: 1, .. })));
+        assert_eq!(parser.line(), 1);
+    }
+
+    #[test]
+    fn symbol_first_token_test() {
+        let parser = Parser::new();
+        let symbol = vec![Token::from(String::from("local"), TokenType::Symbol, false)];
+        let flagged = vec![Token::from(String::from("local"), TokenType::Symbol, true)];
+        let undefined = vec![Token::from(String::from("$%"), TokenType::Undefined, true)];
+        assert!(matches!(parser.parse(&symbol), Err(VmError::Keyword { .. })));
+        assert!(matches!(parser.parse(&flagged), Err(VmError::Keyword { .. })));
+        assert!(matches!(parser.parse(&undefined), Err(VmError::Keyword { .. })));
+    }
+
+    #[test]
+    fn arithmetic_error_category_test() {
+        //A keyword rule that isn't a command type falls through to the arithmetic branch
+        let mut parser = Parser::from(vec![vec![Token::from(String::from("7"), TokenType::Index, true)]], String::from("Main"));
+        match parser.advance() {
+            Err(err @ VmError::Argument { .. }) => {
+                assert_eq!(err.to_string(), "Main: Improper arguments for Arithmetic command at line 1")
+            }
+            other => panic!("Expected an argument error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn error_names_file_test() {
+        let tokenizer = Tokenizer::from(default_ruleset());
+        let tokens = vec![tokenizer.tokenize("pop local").unwrap(), tokenizer.tokenize("LOOP").unwrap()];
+        let mut parser = Parser::from(tokens, String::from("Ball"));
+
+        let argument = parser.advance().unwrap_err().to_string();
+        let keyword = parser.advance().unwrap_err().to_string();
+        assert_eq!(argument, "Ball: Improper arguments for Memory Access command at line 1");
+        assert_eq!(keyword, "Ball: Expected keyword at line 2");
+    }
+
+    #[test]
+    fn display_round_trip_test() {
+        let tokenizer = Tokenizer::from(default_ruleset());
+        let lines = vec![
+            "push local 2",
+            "pop static 3",
+            "add",
+            "if-goto LOOP",
+            "goto END",
+            "label LOOP",
+            "function Main.main 2",
+            "call Math.multiply 2",
+            "return",
+        ];
+        for line in lines {
+            let mut parser = Parser::from(vec![tokenizer.tokenize(line).unwrap()], String::from("Main"));
+            let command = parser.advance().unwrap().unwrap();
+            assert_eq!(command.to_string(), line);
+        }
+
+        let store = Command::Store {
+            segment: String::from("local"),
+            index: 0,
+            value: 7,
+            class_name: String::new(),
+        };
+        let tail_call = Command::TailCall { symbol: String::from("Main.f"), nargs: 2 };
+        for (fused, text) in [(store, "push constant 7\npop local 0"), (tail_call, "call Main.f 2\nreturn")] {
+            assert_eq!(fused.to_string(), text);
+            let parsed: Vec<String> = commands_from_reader(text.as_bytes(), String::from("Main"))
+                .map(|command| command.unwrap().to_string())
+                .collect();
+            assert_eq!(parsed.join("\n"), text);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip_test() {
+        let commands = vec![
+            Command::Function { symbol: String::from("Main.main"), nvars: 1 },
+            Command::Push { segment: String::from("static"), index: 2, class_name: String::from("Main") },
+            Command::Arithmetic(TokenType::Not),
+            Command::If(String::from("END")),
+            Command::Return,
+        ];
+        let json = ::serde_json::to_string(&commands).unwrap();
+        let back: Vec<Command> = ::serde_json::from_str(&json).unwrap();
+        assert_eq!(back, commands);
+    }
+}
@@ -0,0 +1,134 @@
+//End to end checks on the nand2tetris project 7 programs in fixtures/. Each one is
+//translated, assembled to machine code and run on a model of the Hack CPU, and the final RAM
+//has to match both the course's expected values and the interpreter running the same commands
+use assembler::Assembler;
+use interpreter::Vm;
+use parser::{commands_from_reader, Command};
+use vm::translate_str;
+
+//Where the course's test scripts point the segments before running a program
+const SETUP: &[(usize, i16)] = &[(0, 256), (1, 300), (2, 400), (3, 3000), (4, 3010)];
+
+//Enough steps for every fixture. A program still running after this is stuck in a loop
+const STEP_LIMIT: usize = 100_000;
+
+//The Hack CPU, run on assembled machine code. The writer tests use it too
+pub fn run_hack(hack: &str, ram: &mut [i16]) {
+    let program: Vec<u16> = hack.lines().map(|line| u16::from_str_radix(line, 2).unwrap()).collect();
+    let (mut a, mut d, mut pc) = (0i16, 0i16, 0usize);
+    for _ in 0..STEP_LIMIT {
+        if pc >= program.len() {
+            return;
+        }
+        let instruction = program[pc];
+        pc += 1;
+        if instruction & 0x8000 == 0 {
+            a = instruction as i16;
+            continue;
+        }
+
+        //The ALU: zero and/or negate each input, add or and them, then negate the output
+        let bit = |n: u16| instruction & (1 << n) != 0;
+        let address = a as u16 as usize;
+        let mut x = if bit(11) { 0 } else { d };
+        let mut y = if bit(12) { ram[address] } else { a };
+        if bit(10) {
+            x = !x;
+        }
+        if bit(9) {
+            y = 0;
+        }
+        if bit(8) {
+            y = !y;
+        }
+        let mut out = if bit(7) { x.wrapping_add(y) } else { x & y };
+        if bit(6) {
+            out = !out;
+        }
+
+        //M is written through the address A held before this instruction
+        if bit(3) {
+            ram[address] = out;
+        }
+        if bit(5) {
+            a = out;
+        }
+        if bit(4) {
+            d = out;
+        }
+        let jump = (bit(2) && out < 0) || (bit(1) && out == 0) || (bit(0) && out > 0);
+        if jump {
+            pc = address;
+        }
+    }
+    panic!("Program still running after {} steps", STEP_LIMIT);
+}
+
+fn run_translated(source: &str, class_name: &str, args: &[i16]) -> Vec<i16> {
+    let (asm, _) = translate_str(source, class_name, false).unwrap();
+    let hack = Assembler::new().assemble(&asm).unwrap();
+    let mut ram = vec![0i16; 32768];
+    for &(address, value) in SETUP {
+        ram[address] = value;
+    }
+    ram[400..400 + args.len()].copy_from_slice(args);
+    run_hack(&hack, &mut ram);
+    ram
+}
+
+fn run_interpreted(source: &str, class_name: &str, args: &[i16]) -> Vec<i16> {
+    let commands: Vec<Command> = commands_from_reader(source.as_bytes(), class_name.to_string())
+        .map(|command| command.unwrap())
+        .collect();
+    let mut vm = Vm::new(commands).unwrap();
+    for &(address, value) in SETUP {
+        vm.ram_mut()[address] = value;
+    }
+    vm.ram_mut()[400..400 + args.len()].copy_from_slice(args);
+    vm.set_step_limit(STEP_LIMIT);
+    vm.run().unwrap();
+    vm.ram().to_vec()
+}
+
+//Runs a fixture both ways and checks the expected RAM words. The two runs also have to agree
+//on the segment pointers, the statics and the stack, since the writer only keeps scratch
+//values in R13-R15
+fn check_fixture(name: &str, source: &str, args: &[i16], expected: &[(usize, i16)]) {
+    let translated = run_translated(source, name, args);
+    let interpreted = run_interpreted(source, name, args);
+    for &(address, value) in expected {
+        assert_eq!(translated[address], value, "{}: RAM[{}] after translating", name, address);
+        assert_eq!(interpreted[address], value, "{}: RAM[{}] after interpreting", name, address);
+    }
+    assert_eq!(translated[..13], interpreted[..13], "{}: registers", name);
+    let sp = translated[0] as usize;
+    assert_eq!(translated[16..sp], interpreted[16..sp], "{}: statics and stack", name);
+}
+
+#[test]
+fn simple_add() {
+    let source = include_str!("../fixtures/SimpleAdd.vm");
+    check_fixture("SimpleAdd", source, &[], &[(0, 257), (256, 15)]);
+}
+
+#[test]
+fn stack_test() {
+    let source = include_str!("../fixtures/StackTest.vm");
+    let mut expected = vec![(0, 266), (265, -91)];
+    for (i, &value) in [-1, 0, 0, 0, -1, 0, -1, 0, 0].iter().enumerate() {
+        expected.push((256 + i, value));
+    }
+    check_fixture("StackTest", source, &[], &expected);
+}
+
+#[test]
+fn basic_loop() {
+    let source = include_str!("../fixtures/BasicLoop.vm");
+    check_fixture("BasicLoop", source, &[3], &[(0, 257), (256, 6)]);
+}
+
+#[test]
+fn static_test() {
+    let source = include_str!("../fixtures/StaticTest.vm");
+    check_fixture("StaticTest", source, &[], &[(0, 257), (256, 1110)]);
+}
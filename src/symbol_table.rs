@@ -0,0 +1,141 @@
+use error::VmError;
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq)]
+pub struct SymbolTable {
+    symbols: HashMap<String, Address<'static>>,
+    pub current_address: u16,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Address<'a> {
+    Relative(&'a str),
+    Absolute(u16),
+}
+
+impl SymbolTable {
+    const STARTINGTABLE: &'static [(&'static str, &'static Address<'static>)] = &[
+        ("local", &Address::Relative("LCL")),
+        ("argument", &Address::Relative("ARG")),
+        ("this", &Address::Relative("THIS")),
+        ("that", &Address::Relative("THAT")),
+        ("temp", &Address::Absolute(5)),
+        //An extension for low-level testing: the index is the RAM address itself
+        ("ram", &Address::Absolute(0)),
+        //No static entry: each Class.index static is allocated on first use, starting at 16
+    ];
+
+    //Statics have RAM[16-255] to themselves. The stack starts right after
+    pub const STATIC_END: u16 = 255;
+
+    pub fn new() -> SymbolTable {
+        SymbolTable {
+            symbols: HashMap::new(),
+            current_address: 16,
+        }
+    }
+
+    pub fn load_starting_table(&mut self) {
+        for entry in SymbolTable::STARTINGTABLE {
+            self.add_entry(entry.0, *entry.1);
+        }
+    }
+
+    pub fn add_entry(&mut self, symbol: &str, address: Address<'static>) {
+        self.symbols.insert(symbol.to_string(), address);
+    }
+
+    pub fn contains(&self, symbol: &str) -> bool {
+        self.symbols.contains_key(symbol)
+    }
+
+    pub fn get_address(&self, symbol: &str) -> Option<&Address<'static>> {
+        self.symbols.get(symbol)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Address<'static>)> {
+        self.symbols.iter().map(|(symbol, address)| (symbol.as_str(), address))
+    }
+
+    pub fn get_free_address(&self) -> u16 {
+        self.current_address
+    }
+
+    //Gives symbol the next free RAM address
+    pub fn allocate(&mut self, symbol: &str) -> u16 {
+        let addr = self.current_address;
+        self.add_entry(symbol, Address::Absolute(addr));
+        self.current_address += 1;
+        addr
+    }
+
+    //Like allocate, but refuses to hand out an address the stack would overwrite
+    pub fn allocate_static(&mut self, symbol: &str) -> Result<u16, VmError> {
+        if self.current_address > SymbolTable::STATIC_END {
+            return Err(VmError::StaticOverflow(String::from(symbol)));
+        }
+        Ok(self.allocate(symbol))
+    }
+}
+
+impl Default for SymbolTable {
+    fn default() -> SymbolTable {
+        SymbolTable::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn symboltable_new() {
+        let st = SymbolTable::new();
+        assert_eq!(st,SymbolTable{symbols: HashMap::new(), current_address: 16});
+    }
+
+    #[test]
+    fn symboltable_load_starting_table() {
+        let mut st: SymbolTable = SymbolTable::new();
+        st.load_starting_table();
+        assert_eq!(st.get_address("temp").unwrap(), &Address::Absolute(5));
+        assert_eq!(st.get_address("ram").unwrap(), &Address::Absolute(0));
+        assert!(!st.contains("static"));
+    }
+
+    #[test]
+    fn symboltable_add_entry() {
+        let mut st: SymbolTable = SymbolTable::new();
+        st.add_entry("TestAddress", Address::Absolute(12345));
+        assert_eq!(st.get_address("TestAddress").unwrap(), &Address::Absolute(12345));
+    }
+
+    #[test]
+    fn symboltable_allocate() {
+        let mut st: SymbolTable = SymbolTable::new();
+        assert_eq!(st.allocate("Main.0"), 16);
+        assert_eq!(st.allocate("Main.1"), 17);
+        assert_eq!(st.get_address("Main.0").unwrap(), &Address::Absolute(16));
+        assert_eq!(st.get_address("Main.1").unwrap(), &Address::Absolute(17));
+        assert_eq!(st.get_free_address(), 18);
+    }
+
+    #[test]
+    fn symboltable_allocate_static_past_limit() {
+        let mut st: SymbolTable = SymbolTable::new();
+        for i in 16..=SymbolTable::STATIC_END {
+            assert_eq!(st.allocate_static(&format!("Main.{}", i)).unwrap(), i);
+        }
+        let err = st.allocate_static("Main.256").unwrap_err();
+        assert_eq!(err.to_string(), "Out of static memory: Main.256 would be placed at RAM[256]");
+        assert!(!st.contains("Main.256"));
+        assert_eq!(st.get_free_address(), 256);
+    }
+
+    #[test]
+    fn symboltable_contains() {
+        let mut st: SymbolTable = SymbolTable::new();
+        st.add_entry("TestAddress", Address::Absolute(12345));
+        assert_eq!(st.contains("TestAddress"), true);
+    }
+}
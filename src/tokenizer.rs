@@ -0,0 +1,599 @@
+use error::VmError;
+use regex::Regex;
+use std::str::FromStr;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TokenType {
+    Push,
+    Pop,
+    Add,
+    Subtract,
+    Negate,
+    Equal,
+    LessThan,
+    GreaterThan,
+    And,
+    Or,
+    Not,
+    ShiftLeft,
+    ShiftRight,
+    Symbol,
+    Index,
+    Comment,
+    Label,
+    If,
+    Goto,
+    Function,
+    Call,
+    Return,
+    Undefined,
+}
+
+//Parses the names the variants are written with, as in a --rules file
+impl FromStr for TokenType {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<TokenType, ()> {
+        let all = [
+            TokenType::Push, TokenType::Pop, TokenType::Add, TokenType::Subtract, TokenType::Negate,
+            TokenType::Equal, TokenType::LessThan, TokenType::GreaterThan, TokenType::And,
+            TokenType::Or, TokenType::Not, TokenType::ShiftLeft, TokenType::ShiftRight,
+            TokenType::Symbol, TokenType::Index, TokenType::Comment, TokenType::Label, TokenType::If,
+            TokenType::Goto, TokenType::Function, TokenType::Call, TokenType::Return,
+        ];
+        all.iter().copied().find(|t| format!("{:?}", t) == name).ok_or(())
+    }
+}
+
+// Token Struct
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Token {
+    pub token: String,
+    pub token_type: TokenType,
+    pub is_keyword: bool,
+}
+
+impl Token {
+    pub fn new(token_type: TokenType) -> Token {
+        Token {
+            token: String::new(),
+            token_type,
+            is_keyword: false
+        }
+    }
+
+    pub fn from(token: String, token_type: TokenType, is_keyword: bool) -> Token {
+        Token { token, token_type, is_keyword}
+    }
+}
+
+pub type TokenList = Vec<Token>;
+
+//MatchRule Struct
+//A word gets the type of the highest priority rule it matches. Rules with the same priority
+//are tried in the order they were added
+pub struct MatchRule {
+    return_type: TokenType,
+    rule: Regex,
+    is_keyword: bool,
+    priority: u8,
+    //What starts the comment, for Comment rules that match a fixed lead
+    lead: Option<String>,
+}
+
+impl MatchRule {
+    pub const COMMENT_PRIORITY: u8 = 30;
+    pub const KEYWORD_PRIORITY: u8 = 20;
+    pub const DEFAULT_PRIORITY: u8 = 10;
+    //For rules like Symbol and Index that would otherwise swallow keywords
+    pub const CATCH_ALL_PRIORITY: u8 = 0;
+
+    //Keyword rules get KEYWORD_PRIORITY and everything else DEFAULT_PRIORITY
+    pub fn new(return_type: TokenType, rule: Regex, is_keyword: bool) -> MatchRule {
+        let priority = if is_keyword { MatchRule::KEYWORD_PRIORITY } else { MatchRule::DEFAULT_PRIORITY };
+        MatchRule {
+            return_type,
+            rule,
+            is_keyword,
+            priority,
+            lead: None,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: u8) -> MatchRule {
+        self.priority = priority;
+        self
+    }
+
+    //A rule turning any word that starts with lead into a Comment, ending the line
+    pub fn comment(lead: &str) -> MatchRule {
+        let rule = Regex::new(&format!("^{}", ::regex::escape(lead))).unwrap();
+        let mut rule = MatchRule::new(TokenType::Comment, rule, false).with_priority(MatchRule::COMMENT_PRIORITY);
+        rule.lead = Some(String::from(lead));
+        rule
+    }
+
+    pub fn matches_str(&self, input: &str) -> bool {
+        self.rule.is_match(input)
+    }
+}
+
+//Tokenizer Struct
+pub struct Tokenizer {
+    match_rules: Vec<MatchRule>,
+    strict: bool,
+}
+
+impl Tokenizer {
+    pub fn from(match_rules: Vec<MatchRule>) -> Tokenizer {
+        let mut tokenizer = Tokenizer {
+            match_rules: Vec::with_capacity(match_rules.len()),
+            strict: true,
+        };
+        for match_rule in match_rules {
+            tokenizer.add_rule(match_rule);
+        }
+        tokenizer
+    }
+
+    // In strict mode (the default) a word matching no rule is an error. Otherwise it is
+    // passed through as an Undefined token.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    //Goes after every rule of the same or higher priority, so it can't shadow a rule that
+    //was already there at its own level
+    pub fn add_rule(&mut self, match_rule: MatchRule) {
+        let at = self
+            .match_rules
+            .iter()
+            .position(|rule| rule.priority < match_rule.priority)
+            .unwrap_or(self.match_rules.len());
+        self.match_rules.insert(at, match_rule)
+    }
+
+    //The leads of the Comment rules, so block comments can tell when a line comment has begun
+    pub fn comment_leads(&self) -> Vec<String> {
+        self.match_rules.iter().filter_map(|rule| rule.lead.clone()).collect()
+    }
+
+    pub fn tokenize(&self, input: &str) -> Result<TokenList, VmError> {
+        let mut result: TokenList = Vec::new();
+        // Windows-authored files can leave a trailing \r and start with a byte order mark
+        let line = input.trim_end_matches('\r').trim_start_matches('\u{feff}');
+        let word_vec = line.split_whitespace();
+        for word in word_vec {
+            let mut token = Token::new(TokenType::Undefined);
+            for rule in &self.match_rules {
+                if rule.matches_str(word) {
+                    token = Token::from(String::from(word), rule.return_type, rule.is_keyword);
+                    break;
+                }
+            }
+            let t = token.token_type;
+            if t == TokenType::Undefined && self.strict {
+                return Err(VmError::UndefinedToken { word: String::from(word), file: String::new(), line: 0 });
+            }
+            result.push(token);
+            // Stop tokenizing once we hit a comment
+            if t == TokenType::Comment {
+                break;
+            }
+        }
+        Ok(result)
+    }
+}
+
+//What starts a line comment in the default ruleset
+pub const COMMENT_LEADS: &[&str] = &["//", "#"];
+
+pub fn strip_block_comments(lines: Vec<String>, comment_leads: Vec<String>) -> Vec<String> {
+    let mut comments = BlockComments::new(comment_leads);
+    lines.iter().map(|line| comments.strip(line)).collect()
+}
+
+//Removes /* ... */ comments, which may span lines, from lines fed in one at a time. Every
+//input line gives exactly one output line so line numbers don't shift, and a block becomes
+//a space so it still separates the words around it. A /* after one of comment_leads is part
+//of that line comment instead
+#[derive(Debug)]
+pub struct BlockComments {
+    in_block: bool,
+    comment_leads: Vec<String>,
+}
+
+impl BlockComments {
+    pub fn new(comment_leads: Vec<String>) -> BlockComments {
+        BlockComments { in_block: false, comment_leads }
+    }
+
+    pub fn strip(&mut self, line: &str) -> String {
+        let mut kept = String::new();
+        let mut rest: &str = line;
+        loop {
+            if self.in_block {
+                match rest.find("*/") {
+                    Some(i) => {
+                        rest = &rest[i + 2..];
+                        kept.push(' ');
+                        self.in_block = false;
+                    }
+                    None => break,
+                }
+            } else {
+                let block = rest.find("/*");
+                let line_comment = self.comment_leads.iter().filter_map(|lead| rest.find(lead.as_str())).min();
+                match block {
+                    //A /* inside a // comment is just part of the comment
+                    Some(i) if line_comment.is_none_or(|j| i < j) => {
+                        kept.push_str(&rest[..i]);
+                        rest = &rest[i + 2..];
+                        self.in_block = true;
+                    }
+                    _ => {
+                        kept.push_str(rest);
+                        break;
+                    }
+                }
+            }
+        }
+        kept
+    }
+}
+
+//Reads extra rules, one per line: a token type name, a regex without spaces, whether the
+//word is a keyword, and optionally a priority. Blank lines and lines starting with # are
+//skipped. For example, "Subtract ^minus$ true" lets minus stand in for sub. A Comment rule
+//whose pattern is ^ and plain text, like "Comment ^; false", also adds that text as a comment
+//lead for block comments
+pub fn parse_rules(text: &str) -> Result<Vec<MatchRule>, VmError> {
+    let mut rules: Vec<MatchRule> = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() || fields[0].starts_with('#') {
+            continue;
+        }
+        let invalid = || VmError::InvalidRule { line: i + 1, text: String::from(line.trim()) };
+        let (name, pattern, keyword, priority) = match fields[..] {
+            [name, pattern, keyword] => (name, pattern, keyword, None),
+            [name, pattern, keyword, priority] => (name, pattern, keyword, Some(priority)),
+            _ => return Err(invalid()),
+        };
+        let token_type = name.parse::<TokenType>().map_err(|_| invalid())?;
+        let regex = Regex::new(pattern).map_err(|_| invalid())?;
+        let is_keyword = keyword.parse::<bool>().map_err(|_| invalid())?;
+        let mut rule = match comment_lead(token_type, pattern) {
+            Some(lead) => MatchRule::comment(lead),
+            None => MatchRule::new(token_type, regex, is_keyword),
+        };
+        if let Some(priority) = priority {
+            rule = rule.with_priority(priority.parse::<u8>().map_err(|_| invalid())?);
+        }
+        rules.push(rule);
+    }
+    Ok(rules)
+}
+
+fn comment_lead(token_type: TokenType, pattern: &str) -> Option<&str> {
+    let lead = pattern.strip_prefix('^')?;
+    let literal = !lead.is_empty() && !lead.contains(|c| "\\.+*?()|[]{}^$".contains(c));
+    if token_type == TokenType::Comment && literal { Some(lead) } else { None }
+}
+
+pub fn default_ruleset() -> Vec<MatchRule> {
+    let mut rules: Vec<MatchRule> = COMMENT_LEADS.iter().map(|lead| MatchRule::comment(lead)).collect();
+    rules.extend(vec![
+        //Memory Access
+        MatchRule::new(TokenType::Push, Regex::new(r"^push$").unwrap(), true),
+        MatchRule::new(TokenType::Pop, Regex::new(r"^pop$").unwrap(), true),
+        //Arthmetic 
+        MatchRule::new(TokenType::Add, Regex::new(r"^add$").unwrap(), true),
+        MatchRule::new(TokenType::Subtract, Regex::new(r"^sub$").unwrap(), true),
+        MatchRule::new(TokenType::Negate, Regex::new(r"^neg$").unwrap(), true),
+        MatchRule::new(TokenType::Equal, Regex::new(r"^eq$").unwrap(), true),
+        MatchRule::new(TokenType::GreaterThan, Regex::new(r"^gt$").unwrap(), true),
+        MatchRule::new(TokenType::LessThan, Regex::new(r"^lt$").unwrap(), true),
+        MatchRule::new(TokenType::And, Regex::new(r"^and$").unwrap(), true),
+        MatchRule::new(TokenType::Or, Regex::new(r"^or$").unwrap(), true),
+        MatchRule::new(TokenType::Not, Regex::new(r"^not$").unwrap(), true),
+        MatchRule::new(TokenType::ShiftLeft, Regex::new(r"^shiftleft$").unwrap(), true),
+        MatchRule::new(TokenType::ShiftRight, Regex::new(r"^shiftright$").unwrap(), true),
+        //Symbols
+        MatchRule::new(TokenType::Label, Regex::new(r"^label$").unwrap(), true),
+        MatchRule::new(TokenType::If, Regex::new(r"^if-goto$").unwrap(), true),
+        MatchRule::new(TokenType::Goto, Regex::new(r"^goto$").unwrap(), true),
+        MatchRule::new(TokenType::Function, Regex::new(r"^function$").unwrap(), true),
+        MatchRule::new(TokenType::Call, Regex::new(r"^call$").unwrap(), true),
+        MatchRule::new(TokenType::Return, Regex::new(r"^return$").unwrap(), true),
+        //Hack symbols: letters, digits, _ . $ : but no leading digit. Keywords win by priority
+        MatchRule::new(TokenType::Symbol, Regex::new(r"^[a-zA-Z_.$:][a-zA-Z0-9_.$:]*$").unwrap(), false)
+            .with_priority(MatchRule::CATCH_ALL_PRIORITY),
+        MatchRule::new(TokenType::Index, Regex::new(r"^-?[0-9]+$").unwrap(), false)
+            .with_priority(MatchRule::CATCH_ALL_PRIORITY),
+    ]);
+    rules
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn initialize_tokenizer() {
+        let _ = Tokenizer::from(default_ruleset());
+    }
+
+    #[test]
+    fn tokenizer_reused_across_inputs() {
+        let t = Tokenizer::from(default_ruleset());
+        let first = t.tokenize("push local 0").unwrap();
+        let second = t.tokenize("call Main.main 0").unwrap();
+        let again = t.tokenize("push local 0").unwrap();
+        assert_eq!(first, again);
+        assert_eq!(second[0].token_type, TokenType::Call);
+        assert_eq!(second[1].token_type, TokenType::Symbol);
+    }
+
+    #[test]
+    fn token_test1() {
+        let t = Tokenizer::from(default_ruleset());
+        let input = "add eq sub";
+        let result = t.tokenize(input);
+        let test_vec = vec![
+            Token::from(String::from("add"), TokenType::Add, true),
+            Token::from(String::from("eq"), TokenType::Equal, true),
+            Token::from(String::from("sub"), TokenType::Subtract, true),
+        ];
+        assert_eq!(result.unwrap(), test_vec);
+    }
+
+    #[test]
+    fn token_test_undefined() {
+        let mut t = Tokenizer::from(default_ruleset());
+        t.set_strict(false);
+        let input = "add eq %$^%";
+        let result = t.tokenize(input);
+        let test_vec = vec![
+            Token::from(String::from("add"), TokenType::Add, true),
+            Token::from(String::from("eq"), TokenType::Equal, true),
+            Token::from(String::from(""), TokenType::Undefined, false),
+        ];
+        assert_eq!(result.unwrap(), test_vec);
+    }
+
+    #[test]
+    fn token_test_undefined_strict() {
+        let t = Tokenizer::from(default_ruleset());
+        let result = t.tokenize("push loc@l 2");
+        match result {
+            Err(VmError::UndefinedToken { word, .. }) => assert_eq!(word, "loc@l"),
+            other => panic!("Expected an undefined token error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn token_test_dotted_symbols() {
+        let t = Tokenizer::from(default_ruleset());
+        let call = t.tokenize("call Math.multiply 2").unwrap();
+        assert_eq!(call[0].token_type, TokenType::Call);
+        assert_eq!(call[1], Token::from(String::from("Math.multiply"), TokenType::Symbol, false));
+        let function = t.tokenize("function Output.printInt 1").unwrap();
+        assert_eq!(function[1], Token::from(String::from("Output.printInt"), TokenType::Symbol, false));
+    }
+
+    #[test]
+    fn token_test_index_whole_word() {
+        //The sign is kept so the parser can reject it, and trailing junk isn't cut off
+        let t = Tokenizer::from(default_ruleset());
+        let negative = t.tokenize("-1").unwrap();
+        assert_eq!(negative, vec![Token::from(String::from("-1"), TokenType::Index, false)]);
+        match t.tokenize("push local 3x") {
+            Err(VmError::UndefinedToken { word, .. }) => assert_eq!(word, "3x"),
+            other => panic!("Expected an undefined token error, got {:?}", other),
+        }
+        let padded = t.tokenize("007").unwrap();
+        assert_eq!(padded, vec![Token::from(String::from("007"), TokenType::Index, false)]);
+    }
+
+    #[test]
+    fn token_test_crlf_and_bom() {
+        let t = Tokenizer::from(default_ruleset());
+        let crlf = t.tokenize("push\r").unwrap();
+        let bom = t.tokenize("\u{feff}push").unwrap();
+        assert_eq!(crlf.len(), 1);
+        assert_eq!(crlf[0].token_type, TokenType::Push);
+        assert_eq!(bom.len(), 1);
+        assert_eq!(bom[0].token_type, TokenType::Push);
+    }
+
+    fn strip(source: &str) -> Vec<String> {
+        strip_block_comments(source.lines().map(String::from).collect(), Tokenizer::from(default_ruleset()).comment_leads())
+    }
+
+    #[test]
+    fn block_comment_single_line() {
+        assert_eq!(strip("/* setup */\npush/*x*/constant 1"), vec![" ", "push constant 1"]);
+    }
+
+    #[test]
+    fn block_comment_multi_line() {
+        let lines = strip("push constant 1\n/* first\nsecond\nthird */ add\npop local 0");
+        assert_eq!(lines, vec!["push constant 1", "", "", "  add", "pop local 0"]);
+    }
+
+    #[test]
+    fn block_comment_after_command() {
+        let lines = strip("push local 0 /* starts here\nends here */\nadd // not a /* block\nneg");
+        assert_eq!(lines, vec!["push local 0 ", " ", "add // not a /* block", "neg"]);
+    }
+
+    #[test]
+    fn token_test_empty_line() {
+        let t = Tokenizer::from(default_ruleset());
+        let input = "";
+        let result = t.tokenize(input);
+        let test_vec = vec![];
+        assert_eq!(result.unwrap(), test_vec);
+    }
+
+    #[test]
+    fn token_test_memory_command() {
+        let t = Tokenizer::from(default_ruleset());
+        let input = "push local 2";
+        let result = t.tokenize(input);
+        let test_vec = vec![
+            Token::from(String::from("push"), TokenType::Push, true),
+            Token::from(String::from("local"), TokenType::Symbol, false),
+            Token::from(String::from("2"), TokenType::Index, false),
+        ];
+        assert_eq!(result.unwrap(), test_vec);
+    }
+
+    #[test]
+    fn token_test_comment_line() {
+        let t = Tokenizer::from(default_ruleset());
+        let input = "//add eq test";
+        let result = t.tokenize(input);
+        let test_vec = vec![Token::from(String::from("//add"), TokenType::Comment, false)];
+        assert_eq!(result.unwrap(), test_vec);
+    }
+
+    #[test]
+    fn token_test_inline_comment() {
+        let t = Tokenizer::from(default_ruleset());
+        let input = "add eq //test inline doesn't read more symbols";
+        let result = t.tokenize(input);
+        let test_vec = vec![
+            Token::from(String::from("add"), TokenType::Add, true),
+            Token::from(String::from("eq"), TokenType::Equal, true),
+            Token::from(String::from("//test"), TokenType::Comment, false),
+        ];
+        assert_eq!(result.unwrap(), test_vec);
+    }
+
+    #[test]
+    fn token_test_hash_comment_line() {
+        let t = Tokenizer::from(default_ruleset());
+        let result = t.tokenize("# full line");
+        let test_vec = vec![Token::from(String::from("#"), TokenType::Comment, false)];
+        assert_eq!(result.unwrap(), test_vec);
+    }
+
+    #[test]
+    fn token_test_hash_inline_comment() {
+        let t = Tokenizer::from(default_ruleset());
+        let result = t.tokenize("add # inline");
+        let test_vec = vec![
+            Token::from(String::from("add"), TokenType::Add, true),
+            Token::from(String::from("#"), TokenType::Comment, false),
+        ];
+        assert_eq!(result.unwrap(), test_vec);
+        assert_eq!(strip("add # not a /* block"), vec!["add # not a /* block"]);
+    }
+
+    #[test]
+    fn token_test_custom_comment_lead() {
+        let mut t = Tokenizer::from(vec![]);
+        t.add_rule(MatchRule::comment(";"));
+        t.add_rule(MatchRule::new(TokenType::Add, Regex::new(r"^add$").unwrap(), true));
+        let result = t.tokenize("add ;done");
+        assert_eq!(result.unwrap()[1].token_type, TokenType::Comment);
+    }
+
+    #[test]
+    fn block_comments_use_ruleset_leads() {
+        let mut t = Tokenizer::from(default_ruleset());
+        for rule in parse_rules("Comment ^; false\nComment ^(--|;;) false").unwrap() {
+            t.add_rule(rule);
+        }
+        assert_eq!(t.comment_leads(), vec!["//", "#", ";"]);
+        let lines = vec![String::from("add ;not a /* block"), String::from("neg")];
+        assert_eq!(strip_block_comments(lines.clone(), t.comment_leads()), lines);
+        assert_eq!(strip_block_comments(lines, COMMENT_LEADS.iter().map(|l| l.to_string()).collect()), vec!["add ;not a ", ""]);
+    }
+
+    #[test]
+    fn rule_priority_beats_insertion_order() {
+        //A symbol rule listed before the keywords still loses to them
+        let mut rules = vec![MatchRule::new(TokenType::Symbol, Regex::new(r"^[a-z]+$").unwrap(), false)
+            .with_priority(MatchRule::CATCH_ALL_PRIORITY)];
+        rules.extend(default_ruleset());
+        let mut t = Tokenizer::from(rules);
+        assert_eq!(t.tokenize("add").unwrap()[0].token_type, TokenType::Add);
+
+        //A rule added later at a higher priority wins over the keyword it overlaps
+        let alias = Regex::new(r"^add$").unwrap();
+        t.add_rule(MatchRule::new(TokenType::Subtract, alias, true).with_priority(MatchRule::KEYWORD_PRIORITY + 1));
+        assert_eq!(t.tokenize("add").unwrap()[0].token_type, TokenType::Subtract);
+
+        //At the same priority the rule that was there first keeps winning
+        t.add_rule(MatchRule::new(TokenType::Negate, Regex::new(r"^sub$").unwrap(), true));
+        assert_eq!(t.tokenize("sub").unwrap()[0].token_type, TokenType::Subtract);
+    }
+
+    #[test]
+    fn rules_from_text() {
+        let text = "# dialect extensions\nSubtract ^minus$ true\n\nPush ^push$ true 25\n";
+        let mut t = Tokenizer::from(default_ruleset());
+        for rule in parse_rules(text).unwrap() {
+            t.add_rule(rule);
+        }
+        let tokens = t.tokenize("add minus").unwrap();
+        assert_eq!(tokens[1], Token::from(String::from("minus"), TokenType::Subtract, true));
+        assert_eq!(t.tokenize("push").unwrap()[0].token_type, TokenType::Push);
+
+        for bad in &["Minus ^minus$ true", "Subtract ^minus$", "Subtract ^minus$ yes", "Subtract ^(minus$ true"] {
+            match parse_rules(&format!("\n{}", bad)) {
+                Err(VmError::InvalidRule { line: 2, text }) => assert_eq!(&text, bad),
+                other => panic!("Expected an invalid rule error, got {:?}", other.map(|r| r.len())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_alphanumeric_call() {
+        let t = Tokenizer::from(default_ruleset());
+        let input = "call Sys.add12 1";
+        let result = t.tokenize(input);
+        let test_vec = vec![
+            Token::from(String::from("call"), TokenType::Call, true),
+            Token::from(String::from("Sys.add12"), TokenType::Symbol, false),
+            Token::from(String::from("1"), TokenType::Index, false)
+        ];
+        assert_eq!(result.unwrap(), test_vec);
+    }
+
+    #[test]
+    fn test_label_symbols() {
+        let t = Tokenizer::from(default_ruleset());
+        let result = t.tokenize("LOOP loop_2 Ball.new Main$end:1 x push").unwrap();
+        let types: Vec<TokenType> = result.iter().map(|token| token.token_type).collect();
+        assert_eq!(types, vec![
+            TokenType::Symbol,
+            TokenType::Symbol,
+            TokenType::Symbol,
+            TokenType::Symbol,
+            TokenType::Symbol,
+            TokenType::Push,
+        ]);
+        assert!(t.tokenize("2fast").is_err());
+    }
+
+    #[test]
+    fn test_keyword_prefix_is_symbol() {
+        let t = Tokenizer::from(default_ruleset());
+        let input = "pushed order orchestra";
+        let result = t.tokenize(input);
+        let test_vec = vec![
+            Token::from(String::from("pushed"), TokenType::Symbol, false),
+            Token::from(String::from("order"), TokenType::Symbol, false),
+            Token::from(String::from("orchestra"), TokenType::Symbol, false),
+        ];
+        assert_eq!(result.unwrap(), test_vec);
+    }
+}
@@ -0,0 +1,201 @@
+use error::VmError;
+use parser::Command;
+use std::collections::HashSet;
+
+//On failure, the index of the offending command comes back with the error, which the caller
+//can locate in its source
+pub fn validate(commands: &[Command]) -> Result<(), (usize, VmError)> {
+    //First pass: collect every function and every label under the name the writer gives it
+    let mut functions: HashSet<&str> = HashSet::new();
+    let mut labels: HashSet<String> = HashSet::new();
+    let mut current_function: Option<&str> = None;
+    for command in commands {
+        match command {
+            Command::Function { symbol, .. } => {
+                functions.insert(symbol);
+                current_function = Some(symbol);
+            }
+            Command::Label(label) => {
+                labels.insert(scoped_label(current_function, label));
+            }
+            _ => {}
+        }
+    }
+
+    //Second pass: every jump and call has to land on something from the first pass
+    current_function = None;
+    for (i, command) in commands.iter().enumerate() {
+        match command {
+            Command::Function { symbol, .. } => current_function = Some(symbol),
+            Command::Goto(label) | Command::If(label) => {
+                let scoped = scoped_label(current_function, label);
+                if !labels.contains(&scoped) {
+                    return Err((i, VmError::UndefinedLabel { label: scoped, file: String::new(), line: 0 }));
+                }
+            }
+            Command::Call { symbol, .. } | Command::TailCall { symbol, .. }
+                if !functions.contains(symbol.as_str()) =>
+            {
+                return Err((i, VmError::UndefinedFunction { symbol: symbol.clone(), file: String::new(), line: 0 }));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+pub fn unreachable(commands: &[Command]) -> Vec<usize> {
+    //Nothing after a return or goto runs until a label or function gives it an entry point
+    let mut dead: Vec<usize> = vec![];
+    let mut reachable = true;
+    for (i, command) in commands.iter().enumerate() {
+        match command {
+            Command::Label(_) | Command::Function { .. } => reachable = true,
+            _ if !reachable => dead.push(i),
+            Command::Return | Command::Goto(_) | Command::TailCall { .. } => reachable = false,
+            _ => {}
+        }
+    }
+    dead
+}
+
+//Labels and jumps before the first function in a file that has functions. The writer leaves
+//them unscoped, so they can't be reached from any function in that file
+pub fn unscoped_labels(commands: &[Command]) -> Vec<usize> {
+    let first = match commands.iter().position(|c| matches!(c, Command::Function { .. })) {
+        Some(first) => first,
+        None => return vec![],
+    };
+    commands[..first]
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| matches!(c, Command::Label(_) | Command::Goto(_) | Command::If(_)))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+//ram segment accesses below 16, where they can clobber SP, the segment pointers, temp or
+//the writer's scratch registers
+pub fn reserved_ram(commands: &[Command]) -> Vec<usize> {
+    commands
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| match c {
+            Command::Push { segment, index, .. }
+            | Command::Pop { segment, index, .. }
+            | Command::Store { segment, index, .. } => segment == "ram" && *index < 16,
+            _ => false,
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+//The name the writer gives label inside function, as functionName$label
+pub fn scoped_label(function: Option<&str>, label: &str) -> String {
+    match function {
+        Some(function) => format!("{}${}", function, label),
+        None => String::from(label),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokenizer::TokenType;
+
+    fn function(symbol: &str) -> Command {
+        Command::Function { symbol: String::from(symbol), nvars: 0 }
+    }
+
+    #[test]
+    fn defined_targets_are_valid() {
+        let commands = vec![
+            function("Main.main"),
+            Command::Label(String::from("LOOP")),
+            Command::Call { symbol: String::from("Main.helper"), nargs: 0 },
+            Command::If(String::from("LOOP")),
+            Command::Goto(String::from("END")),
+            Command::Label(String::from("END")),
+            Command::Return,
+            function("Main.helper"),
+            Command::Return,
+        ];
+        assert!(validate(&commands).is_ok());
+    }
+
+    #[test]
+    fn undefined_goto_target() {
+        let commands = vec![
+            function("Main.main"),
+            Command::Goto(String::from("MISSING")),
+        ];
+        match validate(&commands) {
+            Err((1, VmError::UndefinedLabel { label, .. })) => assert_eq!(label, "Main.main$MISSING"),
+            other => panic!("Expected an undefined label error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn labels_do_not_leak_between_functions() {
+        let commands = vec![
+            function("Main.main"),
+            Command::Label(String::from("LOOP")),
+            function("Main.other"),
+            Command::If(String::from("LOOP")),
+        ];
+        assert!(matches!(validate(&commands), Err((3, VmError::UndefinedLabel { .. }))));
+    }
+
+    #[test]
+    fn unreachable_after_return_and_goto() {
+        let commands = vec![
+            function("Main.main"),
+            Command::Return,
+            Command::Arithmetic(TokenType::Add),
+            Command::Label(String::from("LOOP")),
+            Command::Goto(String::from("LOOP")),
+            Command::Arithmetic(TokenType::Not),
+            Command::Return,
+            function("Main.other"),
+            Command::Return,
+        ];
+        assert_eq!(unreachable(&commands), vec![2, 5, 6]);
+    }
+
+    #[test]
+    fn labels_before_first_function() {
+        let commands = vec![
+            Command::Label(String::from("TOP")),
+            Command::Goto(String::from("TOP")),
+            function("Main.main"),
+            Command::Label(String::from("LOOP")),
+            Command::Goto(String::from("LOOP")),
+        ];
+        assert_eq!(unscoped_labels(&commands), vec![0, 1]);
+        assert!(unscoped_labels(&commands[..2]).is_empty());
+    }
+
+    #[test]
+    fn ram_below_sixteen_is_reserved() {
+        let ram = |index: u16| Command::Push { segment: String::from("ram"), index, class_name: String::from("Main") };
+        let commands = vec![
+            ram(17),
+            ram(15),
+            Command::Pop { segment: String::from("ram"), index: 0, class_name: String::from("Main") },
+            Command::Pop { segment: String::from("temp"), index: 0, class_name: String::from("Main") },
+        ];
+        assert_eq!(reserved_ram(&commands), vec![1, 2]);
+    }
+
+    #[test]
+    fn undefined_call_target() {
+        let commands = vec![
+            function("Main.main"),
+            Command::Call { symbol: String::from("Nope"), nargs: 0 },
+        ];
+        match validate(&commands) {
+            Err((1, VmError::UndefinedFunction { symbol, .. })) => assert_eq!(symbol, "Nope"),
+            other => panic!("Expected an undefined function error, got {:?}", other),
+        }
+    }
+}
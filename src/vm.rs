@@ -0,0 +1,2139 @@
+use assembler::Assembler;
+use error::VmError;
+use interpreter::Vm;
+use optimizer::optimize;
+use parser::{Command, CommandReader, Parser, SourceCommand};
+use symbol_table::{Address, SymbolTable};
+use tokenizer::{default_ruleset, parse_rules, strip_block_comments, BlockComments, TokenList, Tokenizer};
+use validator::{reserved_ram, unreachable, unscoped_labels, validate};
+use writer::{AsmWriter, RegisterNames, SourceMapEntry, Target};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::prelude::*;
+use std::io;
+use std::io::{BufReader, BufWriter, Result as IOResult};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Verbosity {
+    Quiet,
+    Verbose,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub filevec: Vec<PathBuf>,
+    pub outfile: PathBuf,
+    pub write_init: bool,
+    pub write_hack: bool,
+    pub verbosity: Verbosity,
+    pub read_stdin: bool,
+    pub stdin_name: String,
+    pub class_names: BTreeMap<String, String>,
+    pub comments: bool,
+    pub headers: bool,
+    pub comment_base: u16,
+    pub register_names: RegisterNames,
+    pub target: Target,
+    pub optimize: bool,
+    pub inline_comparisons: bool,
+    pub share_comparisons: bool,
+    pub recurse: bool,
+    pub emit_ast: bool,
+    pub emit_tokens: bool,
+    pub check: bool,
+    pub separate: bool,
+    pub force_init: bool,
+    pub sp_base: u16,
+    pub debug: bool,
+    pub sourcemap: Option<PathBuf>,
+    pub symbols: Option<PathBuf>,
+    pub rules: Option<PathBuf>,
+    pub stats: bool,
+    pub max_count: u16,
+}
+
+impl Config {
+    pub fn new<I: Iterator<Item = String>>(args: I) -> Result<Config, Box<dyn Error>> {
+        let mut args = args.skip(1).collect::<Vec<String>>().into_iter();
+
+        //--help and --version win over everything else, including a missing or bad input path
+        if let Some(arg) = args.clone().find(|arg| EarlyExit::FLAGS.contains(&arg.as_str())) {
+            return Err(Box::new(EarlyExit::from_flag(&arg)));
+        }
+
+        //Anything that isn't a flag or a flag's value is an input path, wherever it appears
+        let mut builder = Config::builder();
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--stdin-name" => match args.next() {
+                    Some(name) => builder.stdin_name(name),
+                    None => return Err(Box::new(InvalidArgError(arg.clone()))),
+                },
+                "--class-name" => match args.next().as_ref().and_then(|pair| pair.split_once('=')) {
+                    Some((stem, name)) => builder.class_name(String::from(stem), String::from(name)),
+                    None => return Err(Box::new(InvalidArgError(arg.clone()))),
+                },
+                "--stdout" => builder.output(PathBuf::from("-")),
+                "--output" | "-o" => match args.next() {
+                    Some(out) => builder.output(PathBuf::from(out)),
+                    None => return Err(Box::new(InvalidArgError(arg.clone()))),
+                },
+                "--init" => builder.write_init(true),
+                "--no-init" => builder.write_init(false),
+                "--hack" => builder.write_hack(true),
+                "--no-comments" => builder.comments(false),
+                "--no-headers" => builder.headers(false),
+                "--comment-base" => match args.next().as_deref() {
+                    Some("0") => builder.comment_base(0),
+                    Some("1") => builder.comment_base(1),
+                    _ => return Err(Box::new(InvalidArgError(arg.clone()))),
+                },
+                "--registers" => match args.next().as_deref() {
+                    Some("symbolic") => builder.register_names(RegisterNames::Symbolic),
+                    Some("numeric") => builder.register_names(RegisterNames::Numeric),
+                    _ => return Err(Box::new(InvalidArgError(arg.clone()))),
+                },
+                "--target" => match args.next().as_deref() {
+                    Some("emulator") => builder.target(Target::Emulator),
+                    Some("strict") => builder.target(Target::Strict),
+                    _ => return Err(Box::new(InvalidArgError(arg.clone()))),
+                },
+                "--optimize" | "-O" => builder.optimize(true),
+                "--inline-comparisons" => builder.inline_comparisons(true),
+                "--share-comparisons" => builder.share_comparisons(true),
+                "--no-recurse" => builder.recurse(false),
+                "--emit-ast" => builder.emit_ast(true),
+                "--emit-tokens" => builder.emit_tokens(true),
+                "--check" => builder.check(true),
+                "--separate" => builder.separate(true),
+                "--force-init" => builder.force_init(true),
+                "--debug" => builder.debug(true),
+                "--stats" => builder.stats(true),
+                "--max-count" => match args.next().and_then(|n| n.parse::<u16>().ok()) {
+                    Some(max_count) => builder.max_count(max_count),
+                    None => return Err(Box::new(InvalidArgError(arg.clone()))),
+                },
+                "--sourcemap" => match args.next() {
+                    Some(path) => builder.sourcemap(PathBuf::from(path)),
+                    None => return Err(Box::new(InvalidArgError(arg.clone()))),
+                },
+                "--symbols" => match args.next() {
+                    Some(path) => builder.symbols(PathBuf::from(path)),
+                    None => return Err(Box::new(InvalidArgError(arg.clone()))),
+                },
+                "--rules" => match args.next() {
+                    Some(path) => builder.rules(PathBuf::from(path)),
+                    None => return Err(Box::new(InvalidArgError(arg.clone()))),
+                },
+                "--sp-base" => match args.next().and_then(|n| n.parse::<u16>().ok()) {
+                    Some(sp_base) => builder.sp_base(sp_base),
+                    None => return Err(Box::new(InvalidArgError(arg.clone()))),
+                },
+                "--verbose" | "-v" => builder.verbose(Verbosity::Verbose),
+                "--quiet" | "-q" => builder.verbose(Verbosity::Quiet),
+                path if path == "-" || !path.starts_with('-') => builder.input(PathBuf::from(path)),
+                _ => return Err(Box::new(InvalidArgError(arg.clone()))),
+            };
+        }
+
+        builder.build()
+    }
+
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder {
+            inputs: vec![],
+            output: None,
+            write_init: true,
+            write_hack: false,
+            verbosity: Verbosity::Quiet,
+            stdin_name: String::from("Stdin"),
+            class_names: BTreeMap::new(),
+            comments: true,
+            headers: true,
+            comment_base: 0,
+            register_names: RegisterNames::Mixed,
+            target: Target::Emulator,
+            optimize: false,
+            inline_comparisons: false,
+            share_comparisons: false,
+            recurse: true,
+            emit_ast: false,
+            emit_tokens: false,
+            check: false,
+            separate: false,
+            force_init: false,
+            sp_base: 256,
+            debug: false,
+            sourcemap: None,
+            symbols: None,
+            rules: None,
+            stats: false,
+            max_count: Parser::DEFAULT_MAX_COUNT,
+        }
+    }
+
+    //The class a file's statics belong to: its stem, unless --class-name renamed it
+    fn class_name(&self, path: &Path) -> String {
+        let stem = String::from(uncompressed(path).file_stem().unwrap().to_string_lossy());
+        match self.class_names.get(&stem) {
+            Some(name) => name.clone(),
+            None => stem,
+        }
+    }
+
+    //Sources are keyed by class name, so two inputs with the same one would lose one of them.
+    //This catches a/Main.vm beside b/Main.vm, Main.vm beside Main.vm.gz, and stdin named
+    //after a file's class
+    fn check_class_names(&self) -> Result<(), DuplicateClassError> {
+        let mut seen: BTreeMap<String, PathBuf> = BTreeMap::new();
+        if self.read_stdin {
+            seen.insert(self.stdin_name.clone(), PathBuf::from("-"));
+        }
+        for path in &self.filevec {
+            let name = self.class_name(path);
+            if let Some(first) = seen.get(&name) {
+                return Err(DuplicateClassError { class_name: name, first: first.clone(), second: path.clone() });
+            }
+            seen.insert(name, path.clone());
+        }
+        Ok(())
+    }
+}
+
+//Builds a Config without going through command line arguments. Each setter matches a flag
+//of Config::new, and build applies the same checks.
+#[derive(Debug)]
+pub struct ConfigBuilder {
+    inputs: Vec<PathBuf>,
+    output: Option<PathBuf>,
+    write_init: bool,
+    write_hack: bool,
+    verbosity: Verbosity,
+    stdin_name: String,
+    class_names: BTreeMap<String, String>,
+    comments: bool,
+    headers: bool,
+    comment_base: u16,
+    register_names: RegisterNames,
+    target: Target,
+    optimize: bool,
+    inline_comparisons: bool,
+    share_comparisons: bool,
+    recurse: bool,
+    emit_ast: bool,
+    emit_tokens: bool,
+    check: bool,
+    separate: bool,
+    force_init: bool,
+    sp_base: u16,
+    debug: bool,
+    sourcemap: Option<PathBuf>,
+    symbols: Option<PathBuf>,
+    rules: Option<PathBuf>,
+    stats: bool,
+    max_count: u16,
+}
+
+impl ConfigBuilder {
+    //A .vm file, a directory of them, a pattern, or "-" for stdin. Can be given more than
+    //once, and the default output is named after the first
+    pub fn input(&mut self, input: PathBuf) -> &mut ConfigBuilder {
+        self.inputs.push(input);
+        self
+    }
+
+    //"-" writes to stdout
+    pub fn output(&mut self, output: PathBuf) -> &mut ConfigBuilder {
+        self.output = Some(output);
+        self
+    }
+
+    pub fn write_init(&mut self, write_init: bool) -> &mut ConfigBuilder {
+        self.write_init = write_init;
+        self
+    }
+
+    pub fn write_hack(&mut self, write_hack: bool) -> &mut ConfigBuilder {
+        self.write_hack = write_hack;
+        self
+    }
+
+    pub fn verbose(&mut self, verbosity: Verbosity) -> &mut ConfigBuilder {
+        self.verbosity = verbosity;
+        self
+    }
+
+    pub fn stdin_name(&mut self, stdin_name: String) -> &mut ConfigBuilder {
+        self.stdin_name = stdin_name;
+        self
+    }
+
+    //Qualify statics in the file named stem.vm with class_name instead of the file name
+    pub fn class_name(&mut self, stem: String, class_name: String) -> &mut ConfigBuilder {
+        self.class_names.insert(stem, class_name);
+        self
+    }
+
+    pub fn comments(&mut self, comments: bool) -> &mut ConfigBuilder {
+        self.comments = comments;
+        self
+    }
+
+    //Whether each command starts with a //Command #n line
+    pub fn headers(&mut self, headers: bool) -> &mut ConfigBuilder {
+        self.headers = headers;
+        self
+    }
+
+    //The number of the first //Command #n line
+    pub fn comment_base(&mut self, comment_base: u16) -> &mut ConfigBuilder {
+        self.comment_base = comment_base;
+        self
+    }
+
+    //Write every predefined register by name or every one by address
+    pub fn register_names(&mut self, register_names: RegisterNames) -> &mut ConfigBuilder {
+        self.register_names = register_names;
+        self
+    }
+
+    //Which assembler the output has to suit
+    pub fn target(&mut self, target: Target) -> &mut ConfigBuilder {
+        self.target = target;
+        self
+    }
+
+    pub fn optimize(&mut self, optimize: bool) -> &mut ConfigBuilder {
+        self.optimize = optimize;
+        self
+    }
+
+    pub fn inline_comparisons(&mut self, inline_comparisons: bool) -> &mut ConfigBuilder {
+        self.inline_comparisons = inline_comparisons;
+        self
+    }
+
+    //Only matters for inline comparisons, including the ones --separate always writes
+    pub fn share_comparisons(&mut self, share_comparisons: bool) -> &mut ConfigBuilder {
+        self.share_comparisons = share_comparisons;
+        self
+    }
+
+    pub fn recurse(&mut self, recurse: bool) -> &mut ConfigBuilder {
+        self.recurse = recurse;
+        self
+    }
+
+    pub fn emit_ast(&mut self, emit_ast: bool) -> &mut ConfigBuilder {
+        self.emit_ast = emit_ast;
+        self
+    }
+
+    pub fn emit_tokens(&mut self, emit_tokens: bool) -> &mut ConfigBuilder {
+        self.emit_tokens = emit_tokens;
+        self
+    }
+
+    pub fn check(&mut self, check: bool) -> &mut ConfigBuilder {
+        self.check = check;
+        self
+    }
+
+    pub fn separate(&mut self, separate: bool) -> &mut ConfigBuilder {
+        self.separate = separate;
+        self
+    }
+
+    //Write the bootstrap even when no Sys.init is defined
+    pub fn force_init(&mut self, force_init: bool) -> &mut ConfigBuilder {
+        self.force_init = force_init;
+        self
+    }
+
+    //Initial stack pointer written by the bootstrap
+    pub fn sp_base(&mut self, sp_base: u16) -> &mut ConfigBuilder {
+        self.sp_base = sp_base;
+        self
+    }
+
+    //Step through the program in the interpreter instead of translating it
+    pub fn debug(&mut self, debug: bool) -> &mut ConfigBuilder {
+        self.debug = debug;
+        self
+    }
+
+    //Also write a TSV mapping .asm line ranges back to the VM commands they came from
+    pub fn sourcemap(&mut self, sourcemap: PathBuf) -> &mut ConfigBuilder {
+        self.sourcemap = Some(sourcemap);
+        self
+    }
+
+    //Also write the labels and variables the assembler resolved. Needs write_hack
+    pub fn symbols(&mut self, symbols: PathBuf) -> &mut ConfigBuilder {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    //A file of extra token rules to add to the default ruleset. See tokenizer::parse_rules
+    pub fn rules(&mut self, rules: PathBuf) -> &mut ConfigBuilder {
+        self.rules = Some(rules);
+        self
+    }
+
+    //Print file, command and code size counts to stderr once the translation is done
+    pub fn stats(&mut self, stats: bool) -> &mut ConfigBuilder {
+        self.stats = stats;
+        self
+    }
+
+    //Highest nvars or nargs a function or call may use
+    pub fn max_count(&mut self, max_count: u16) -> &mut ConfigBuilder {
+        self.max_count = max_count;
+        self
+    }
+
+    fn input_vmfiles(&self, path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        match path.is_dir() {
+            _ if is_glob(path) => match glob_vmfiles(path)? {
+                ref files if files.is_empty() => Err(Box::new(FileTypeError)),
+                files => Ok(files),
+            },
+            //An empty directory is almost always the wrong path, so don't write an empty program
+            true => match get_vmfiles_in_path(path.to_path_buf(), self.recurse)? {
+                ref files if files.is_empty() => Err(Box::new(NoVmFilesError(path.to_path_buf()))),
+                files => Ok(files),
+            },
+            false => {
+                if is_vmfile(path) {
+                    info(self.verbosity, &format!("Adding File: {}", path.to_str().unwrap()));
+                    Ok(vec![path.to_path_buf()])
+                } else {
+                    Err(Box::new(FileTypeError))
+                }
+            }
+        }
+    }
+
+    pub fn build(&self) -> Result<Config, Box<dyn Error>> {
+        let path = match self.inputs.first() {
+            Some(path) => path.clone(),
+            None => return Err(Box::new(FileTypeError)),
+        };
+
+        // "-" reads VM commands from stdin, writing to stdout unless an output is given
+        let read_stdin = self.inputs.iter().any(|input| input.as_os_str() == "-");
+        let outfile = match &self.output {
+            Some(out) => out.clone(),
+            None if read_stdin || self.emit_tokens => PathBuf::from("-"),
+            None if is_glob(&path) => default_outfile(&glob_dir(&path)),
+            None if self.emit_ast => default_outfile(&path).with_extension("json"),
+            None => default_outfile(&path),
+        };
+        //The stack has to live in data memory, below the screen map at 16384
+        if self.sp_base >= 16384 {
+            return Err(Box::new(InvalidArgError(format!("--sp-base {}", self.sp_base))));
+        }
+        //The debugger reads its commands from stdin, so the program can't come from there
+        if self.debug && read_stdin {
+            return Err(Box::new(InvalidArgError(String::from("--debug"))));
+        }
+        //The JSON dump needs the serde feature
+        if self.emit_ast && !cfg!(feature = "serde") {
+            return Err(Box::new(InvalidArgError(String::from("--emit-ast"))));
+        }
+        if self.write_hack && (outfile.as_os_str() == "-" || self.emit_ast || self.emit_tokens) {
+            return Err(Box::new(InvalidArgError(String::from("--hack"))));
+        }
+        //Separate outputs sit next to their .vm files, so they need real files and plain assembly
+        let dumps = self.emit_ast || self.emit_tokens || self.write_hack;
+        if self.separate && (read_stdin || self.output.is_some() || dumps) {
+            return Err(Box::new(InvalidArgError(String::from("--separate"))));
+        }
+        //The map covers one .asm file, and optimized commands have no single source line
+        let unmapped = self.separate || self.optimize || self.check || self.emit_ast || self.emit_tokens;
+        if self.sourcemap.is_some() && unmapped {
+            return Err(Box::new(InvalidArgError(String::from("--sourcemap"))));
+        }
+        //Symbols come out of the assembler, so there has to be a .hack file
+        if self.symbols.is_some() && !self.write_hack {
+            return Err(Box::new(InvalidArgError(String::from("--symbols"))));
+        }
+        //Token and JSON dumps don't translate anything to count
+        if self.stats && (self.emit_ast || self.emit_tokens) {
+            return Err(Box::new(InvalidArgError(String::from("--stats"))));
+        }
+
+        //A file named twice, directly or through a directory, is only read once
+        let mut filevec: Vec<PathBuf> = vec![];
+        for input in self.inputs.iter().filter(|input| input.as_os_str() != "-") {
+            for file in self.input_vmfiles(input)? {
+                if !filevec.contains(&file) {
+                    filevec.push(file);
+                }
+            }
+        }
+
+        let config = Config {
+            filevec,
+            outfile,
+            write_init: self.write_init,
+            write_hack: self.write_hack,
+            verbosity: self.verbosity,
+            read_stdin,
+            stdin_name: self.stdin_name.clone(),
+            class_names: self.class_names.clone(),
+            comments: self.comments,
+            headers: self.headers,
+            comment_base: self.comment_base,
+            register_names: self.register_names,
+            target: self.target,
+            optimize: self.optimize,
+            inline_comparisons: self.inline_comparisons,
+            share_comparisons: self.share_comparisons,
+            recurse: self.recurse,
+            emit_ast: self.emit_ast,
+            emit_tokens: self.emit_tokens,
+            check: self.check,
+            separate: self.separate,
+            force_init: self.force_init,
+            sp_base: self.sp_base,
+            debug: self.debug,
+            sourcemap: self.sourcemap.clone(),
+            symbols: self.symbols.clone(),
+            rules: self.rules.clone(),
+            stats: self.stats,
+            max_count: self.max_count,
+        };
+        config.check_class_names()?;
+        Ok(config)
+    }
+}
+
+//What a run read and wrote. bytes_written counts the assembly (or token or JSON dump) only,
+//not a .hack file or source map written alongside it
+#[derive(Debug, PartialEq, Default)]
+pub struct RunReport {
+    pub files: usize,
+    pub commands: usize,
+    pub bytes_written: usize,
+    pub warnings: Vec<Warning>,
+}
+
+//Something suspect in a program that still translates
+#[derive(Debug, PartialEq, Clone)]
+pub enum Warning {
+    UnscopedLabel { file: String, line: usize, source: String },
+    Unreachable { file: String, line: usize, source: String },
+    ReservedRam { file: String, line: usize, source: String },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::UnscopedLabel { file, line, source } => {
+                write!(f, "Warning: {}: label outside any function at line {}: {}", file, line, source)
+            }
+            Warning::Unreachable { file, line, source } => {
+                write!(f, "Warning: {}: unreachable command at line {}: {}", file, line, source)
+            }
+            Warning::ReservedRam { file, line, source } => {
+                write!(f, "Warning: {}: ram access to a reserved register at line {}: {}", file, line, source)
+            }
+        }
+    }
+}
+
+//Warnings are printed once the run is over, so they don't interleave with verbose logging
+pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let report = run_with_report(config)?;
+    for warning in &report.warnings {
+        eprintln!("{}", warning);
+    }
+    Ok(())
+}
+
+pub fn run_with_report(config: Config) -> Result<RunReport, Box<dyn Error>> {
+    let report = run_config(&config)?;
+    info(
+        config.verbosity,
+        &format!(
+            "Wrote {} bytes for {} commands from {} files",
+            report.bytes_written, report.commands, report.files
+        ),
+    );
+    Ok(report)
+}
+
+fn run_config(config: &Config) -> Result<RunReport, Box<dyn Error>> {
+    // --check runs the whole translation but throws the output away
+    if config.check {
+        let report = translate(config, &mut io::sink())?;
+        println!("OK");
+        return Ok(RunReport { bytes_written: 0, ..report });
+    }
+
+    if config.separate {
+        return translate_separate(config);
+    }
+
+    //The debugger doesn't translate anything, so there is nothing to report
+    if config.debug {
+        let mut vm = load_vm(config)?;
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        debug_session(&mut vm, stdin.lock(), stdout.lock())?;
+        return Ok(RunReport::default());
+    }
+
+    // An output path of "-" streams the assembly to stdout
+    if config.outfile.as_os_str() == "-" {
+        let stdout = io::stdout();
+        return write_asm_file(config, stdout.lock());
+    }
+
+    let report = write_asm_file(config, BufWriter::new(fs::File::create(&config.outfile)?))?;
+
+    if config.write_hack {
+        let asm = fs::read_to_string(&config.outfile)?;
+        let mut assembler = Assembler::new();
+        let hack = assembler.assemble(&asm)?;
+        fs::write(config.outfile.with_extension("hack"), hack)?;
+        if let Some(path) = &config.symbols {
+            fs::write(path, assembler.symbols())?;
+        }
+    }
+    Ok(report)
+}
+
+fn translate<W: Write>(config: &Config, out: &mut W) -> Result<RunReport, Box<dyn Error>> {
+    let mut out = CountingWriter::new(out);
+    let report = if config.emit_tokens {
+        dump_tokens(config, &mut out)?
+    } else {
+        write_program(config, read_sources(config)?, &mut out)?
+    };
+    Ok(RunReport { bytes_written: out.count, ..report })
+}
+
+//Passes writes through, keeping a count of the bytes
+struct CountingWriter<W: Write> {
+    inner: W,
+    count: usize,
+}
+
+impl<W: Write> CountingWriter<W> {
+    fn new(inner: W) -> CountingWriter<W> {
+        CountingWriter { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IOResult<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> IOResult<()> {
+        self.inner.flush()
+    }
+}
+
+//Reads each file a line at a time, tokenizing and parsing as it goes, so neither the raw
+//source nor its tokens are ever held for a whole file. Only the parsed commands are kept:
+//validation and the bootstrap both need the whole program before anything is written
+fn read_sources(config: &Config) -> Result<Vec<(String, Vec<SourceCommand>)>, VmError> {
+    let tokenizer = new_tokenizer(config)?;
+    let mut file_map: BTreeMap<String, Vec<SourceCommand>> = read_files(config, &tokenizer)?.into_iter().collect();
+    if config.read_stdin {
+        let stdin = io::stdin();
+        let cl = read_commands(config, &tokenizer, &config.stdin_name, stdin.lock())?;
+        file_map.insert(config.stdin_name.clone(), cl);
+    }
+    Ok(file_map.into_iter().collect())
+}
+
+fn read_file(config: &Config, tokenizer: &Tokenizer, filename: &Path) -> Result<(String, Vec<SourceCommand>), VmError> {
+    info(config.verbosity, &format!("Loading file {}", filename.to_str().unwrap()));
+    let name = config.class_name(filename);
+    let cl = read_commands(config, tokenizer, &name, open_source(filename)?)?;
+    Ok((name, cl))
+}
+
+fn read_files(config: &Config, tokenizer: &Tokenizer) -> Result<Vec<(String, Vec<SourceCommand>)>, VmError> {
+    let read = |filename: &PathBuf| read_file(config, tokenizer, filename);
+    #[cfg(feature = "rayon")]
+    return {
+        use rayon::prelude::*;
+        config.filevec.par_iter().map(read).collect()
+    };
+    #[cfg(not(feature = "rayon"))]
+    return config.filevec.iter().map(read).collect();
+}
+
+fn read_commands<R: BufRead>(
+    config: &Config,
+    tokenizer: &Tokenizer,
+    name: &str,
+    reader: R,
+) -> Result<Vec<SourceCommand>, VmError> {
+    let mut commands = CommandReader::new(reader, String::from(name), tokenizer);
+    commands.set_max_count(config.max_count);
+    commands.collect()
+}
+
+//A .vm.gz file is decompressed as it is read
+fn open_source(path: &Path) -> IOResult<Box<dyn BufRead>> {
+    let file = fs::File::open(path)?;
+    #[cfg(feature = "gzip")]
+    {
+        if is_gzip(path) {
+            return Ok(Box::new(BufReader::new(::flate2::read::GzDecoder::new(file))));
+        }
+    }
+    Ok(Box::new(BufReader::new(file)))
+}
+
+//Tokens are written out a line at a time as each file is read, in the same name order the
+//translation uses
+fn dump_tokens<W: Write>(config: &Config, out: &mut W) -> Result<RunReport, Box<dyn Error>> {
+    let tokenizer = new_tokenizer(config)?;
+    let mut paths: BTreeMap<String, Option<&PathBuf>> = BTreeMap::new();
+    if config.read_stdin {
+        paths.insert(config.stdin_name.clone(), None);
+    }
+    for filename in &config.filevec {
+        paths.insert(config.class_name(filename), Some(filename));
+    }
+    for (name, path) in &paths {
+        match path {
+            Some(path) => {
+                info(config.verbosity, &format!("Loading file {}", path.display()));
+                write_tokens(name, &tokenizer, open_source(path)?, out)?;
+            }
+            None => write_tokens(name, &tokenizer, io::stdin().lock(), out)?,
+        }
+    }
+    Ok(RunReport { files: paths.len(), ..RunReport::default() })
+}
+
+fn write_program<W: Write>(
+    config: &Config,
+    files: Vec<(String, Vec<SourceCommand>)>,
+    out: &mut W,
+) -> Result<RunReport, Box<dyn Error>> {
+    let warnings = check_program(&files)?;
+    let stats = Stats::from_files(&files);
+    let report = RunReport { files: stats.files, commands: stats.command_count(), bytes_written: 0, warnings };
+    if config.emit_ast {
+        let commands: Vec<Command> = files.into_iter().flat_map(|(_, cl)| cl).map(|c| c.command).collect();
+        emit_ast(&commands, out)?;
+        return Ok(report);
+    }
+
+    let mut writer = new_writer(config);
+    if wants_init(config, &files) {
+        out.write_all(writer.write_init()?.as_bytes())?;
+    }
+    for (name, cl) in files {
+        writer.set_source_file(&format!("{}.vm", name));
+        write_commands(config, &mut writer, cl, out)?;
+    }
+    out.write_all(writer.write_routines().as_bytes())?;
+    if config.stats {
+        eprint!("{}", stats.with_writer(&writer));
+    }
+
+    if let (Some(path), Some(entries)) = (&config.sourcemap, writer.source_map()) {
+        write_source_map(entries, BufWriter::new(fs::File::create(path)?))?;
+    }
+
+    Ok(report)
+}
+
+fn translate_separate(config: &Config) -> Result<RunReport, Box<dyn Error>> {
+    //Every file gets its own .asm, but they all go through one writer so labels and static
+    //addresses stay unique across the whole program. Comparisons are inlined, since a
+    //shared routine would only exist in one of the files. The bootstrap goes in the first.
+    let files = read_sources(config)?;
+    let warnings = check_program(&files)?;
+    let write_init = wants_init(config, &files);
+    let mut writer = new_writer(config);
+    writer.set_inline_comparisons(true);
+    let stats = Stats::from_files(&files);
+    let mut report = RunReport { files: stats.files, commands: stats.command_count(), bytes_written: 0, warnings };
+
+    for (i, (name, cl)) in files.into_iter().enumerate() {
+        let path = match config.filevec.iter().find(|path| config.class_name(path) == name) {
+            Some(path) => uncompressed(path).with_extension("asm"),
+            //Only stdin has no path, and --separate doesn't read it. Skipping the file would
+            //also drop the bootstrap if it came first
+            None => return Err(Box::new(UnknownClassError(name))),
+        };
+        info(config.verbosity, &format!("Writing {}", path.display()));
+        let mut out = CountingWriter::new(BufWriter::new(fs::File::create(&path)?));
+        writer.set_source_file(&format!("{}.vm", name));
+        if i == 0 && write_init {
+            out.write_all(writer.write_init()?.as_bytes())?;
+        }
+        write_commands(config, &mut writer, cl, &mut out)?;
+        out.flush()?;
+        report.bytes_written += out.count;
+    }
+    if config.stats {
+        eprint!("{}", stats.with_writer(&writer));
+    }
+    Ok(report)
+}
+
+//Counts reported by --stats. Commands are counted as parsed, before any optimization
+#[derive(Debug, PartialEq)]
+struct Stats {
+    files: usize,
+    commands: BTreeMap<&'static str, usize>,
+    asm_lines: usize,
+    branches: usize,
+}
+
+impl Stats {
+    fn from_files(files: &[(String, Vec<SourceCommand>)]) -> Stats {
+        let mut commands = BTreeMap::new();
+        for comm in files.iter().flat_map(|(_, cl)| cl) {
+            *commands.entry(command_kind(&comm.command)).or_insert(0) += 1;
+        }
+        Stats { files: files.len(), commands, asm_lines: 0, branches: 0 }
+    }
+
+    fn with_writer(mut self, writer: &AsmWriter) -> Stats {
+        self.asm_lines = writer.asm_lines();
+        self.branches = writer.branch_count();
+        self
+    }
+
+    fn command_count(&self) -> usize {
+        self.commands.values().sum()
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Files: {}", self.files)?;
+        writeln!(f, "Commands: {}", self.command_count())?;
+        for (kind, count) in &self.commands {
+            writeln!(f, "  {}: {}", kind, count)?;
+        }
+        writeln!(f, "Assembly lines: {}", self.asm_lines)?;
+        writeln!(f, "Branches: {}", self.branches)
+    }
+}
+
+fn command_kind(command: &Command) -> &'static str {
+    match command {
+        Command::Push { .. } => "push",
+        Command::Pop { .. } => "pop",
+        Command::Arithmetic(_) => "arithmetic",
+        Command::Goto(_) => "goto",
+        Command::If(_) => "if-goto",
+        Command::Label(_) => "label",
+        Command::Function { .. } => "function",
+        Command::Call { .. } => "call",
+        Command::Return => "return",
+        Command::Store { .. } => "store",
+        Command::TailCall { .. } => "tail call",
+    }
+}
+
+fn wants_init(config: &Config, files: &[(String, Vec<SourceCommand>)]) -> bool {
+    //The bootstrap calls Sys.init, so without one (project 7 style programs) it would jump
+    //to a label that doesn't exist
+    let has_sys_init = files.iter().flat_map(|(_, cl)| cl).any(|c| match &c.command {
+        Command::Function { symbol, .. } => symbol == "Sys.init",
+        _ => false,
+    });
+    config.write_init && (has_sys_init || config.force_init)
+}
+
+fn load_vm(config: &Config) -> Result<Vm, Box<dyn Error>> {
+    let files = read_sources(config)?;
+    //There is no report to carry these, and the session is about to start
+    for warning in check_program(&files)? {
+        eprintln!("{}", warning);
+    }
+    let bootstrap = wants_init(config, &files);
+    let commands: Vec<Command> = files.into_iter().flat_map(|(_, cl)| cl).map(|c| c.command).collect();
+    let mut vm = Vm::new(commands)?;
+    if bootstrap {
+        vm.bootstrap()?;
+    }
+    Ok(vm)
+}
+
+fn debug_session<R: BufRead, W: Write>(vm: &mut Vm, input: R, mut out: W) -> Result<(), Box<dyn Error>> {
+    writeln!(out, "Commands: s(tep) or enter, c(ontinue), p(rint), q(uit)")?;
+    let mut lines = input.lines();
+    loop {
+        if vm.halted() {
+            writeln!(out, "Halted")?;
+            write_vm_state(vm, &mut out)?;
+            return Ok(());
+        }
+        write!(out, "{}: {}\n> ", vm.pc(), vm.current_command().unwrap())?;
+        out.flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(()),
+        };
+        match line.trim() {
+            "" | "s" | "step" => vm.step()?,
+            "c" | "continue" => vm.run()?,
+            "p" | "print" => write_vm_state(vm, &mut out)?,
+            "q" | "quit" => return Ok(()),
+            other => writeln!(out, "Unknown command: {}", other)?,
+        }
+    }
+}
+
+fn write_vm_state<W: Write>(vm: &Vm, out: &mut W) -> IOResult<()> {
+    let bases = vm.segment_bases();
+    writeln!(out, "SP={} LCL={} ARG={} THIS={} THAT={}", vm.sp(), bases[0], bases[1], bases[2], bases[3])?;
+    writeln!(out, "Stack top: {:?}", vm.top(8))
+}
+
+fn new_writer(config: &Config) -> AsmWriter {
+    let mut st: SymbolTable = SymbolTable::new();
+    st.load_starting_table();
+    let mut writer: AsmWriter = AsmWriter::from(st);
+    writer.set_comments(config.comments);
+    writer.set_headers(config.headers);
+    writer.set_comment_base(config.comment_base);
+    writer.set_register_names(config.register_names);
+    writer.set_target(config.target);
+    writer.set_inline_comparisons(config.inline_comparisons);
+    writer.set_share_comparisons(config.share_comparisons);
+    writer.set_sp_base(config.sp_base);
+    writer.set_source_map(config.sourcemap.is_some());
+    writer
+}
+
+fn write_source_map<W: Write>(entries: &[SourceMapEntry], mut out: W) -> IOResult<()> {
+    //One line per command: first and last .asm line, then the .vm file, line and command
+    for entry in entries {
+        writeln!(out, "{}\t{}\t{}\t{}\t{}", entry.start, entry.end, entry.file, entry.line, entry.command)?;
+    }
+    out.flush()
+}
+
+//One tokenizer for every file, so the ruleset's regexes are only compiled once
+fn new_tokenizer(config: &Config) -> Result<Tokenizer, VmError> {
+    let mut tokenizer = Tokenizer::from(default_ruleset());
+    if let Some(path) = &config.rules {
+        for rule in parse_rules(&fs::read_to_string(path)?)? {
+            tokenizer.add_rule(rule);
+        }
+    }
+    //The token dump shows undefined words rather than stopping at them
+    tokenizer.set_strict(!config.emit_tokens);
+    Ok(tokenizer)
+}
+
+//Validation looks at the whole program at once. Anything that doesn't stop translation
+//comes back as a warning
+fn check_program(files: &[(String, Vec<SourceCommand>)]) -> Result<Vec<Warning>, VmError> {
+    let mut warnings: Vec<Warning> = vec![];
+    for (filename, cl) in files {
+        let commands: Vec<Command> = cl.iter().map(|c| c.command.clone()).collect();
+        for i in unscoped_labels(&commands) {
+            warnings.push(Warning::UnscopedLabel {
+                file: filename.clone(),
+                line: cl[i].line,
+                source: cl[i].source.clone(),
+            });
+        }
+        for i in reserved_ram(&commands) {
+            warnings.push(Warning::ReservedRam {
+                file: filename.clone(),
+                line: cl[i].line,
+                source: cl[i].source.clone(),
+            });
+        }
+    }
+
+    //Flattened with each command's file, so warnings still point into the right one
+    let all: Vec<(&String, &SourceCommand)> =
+        files.iter().flat_map(|(file, cl)| cl.iter().map(move |c| (file, c))).collect();
+    let commands: Vec<Command> = all.iter().map(|(_, c)| c.command.clone()).collect();
+    validate(&commands).map_err(|(i, err)| err.located(all[i].0, all[i].1.line))?;
+    for i in unreachable(&commands) {
+        let (file, c) = all[i];
+        warnings.push(Warning::Unreachable { file: file.clone(), line: c.line, source: c.source.clone() });
+    }
+    Ok(warnings)
+}
+
+fn write_commands<W: Write>(
+    config: &Config,
+    writer: &mut AsmWriter,
+    cl: Vec<SourceCommand>,
+    out: &mut W,
+) -> Result<(), VmError> {
+    if config.optimize {
+        //Fused commands no longer map to a single source line, so they aren't annotated
+        for comm in optimize(cl.into_iter().map(|c| c.command).collect()) {
+            writer.write_command_to(comm, out)?;
+        }
+    } else {
+        for comm in cl {
+            writer.write_source_command_to(comm, out)?;
+        }
+    }
+    Ok(())
+}
+
+//One line per non-empty source line: each token's text, type and whether it is a keyword
+fn write_tokens<R: BufRead, W: Write>(
+    filename: &str,
+    tokenizer: &Tokenizer,
+    reader: R,
+    out: &mut W,
+) -> Result<(), VmError> {
+    let mut comments = BlockComments::new(tokenizer.comment_leads());
+    for (i, line) in reader.lines().enumerate() {
+        let token_list = tokenizer.tokenize(&comments.strip(&line?)).map_err(|err| err.located(filename, i + 1))?;
+        if token_list.is_empty() {
+            continue;
+        }
+        let tokens: Vec<String> = token_list
+            .iter()
+            .map(|t| format!("{} {:?} {}", t.token, t.token_type, t.is_keyword))
+            .collect();
+        writeln!(out, "{}:{}: {}", filename, i + 1, tokens.join(", "))?;
+    }
+    Ok(())
+}
+
+//Dumps the parsed commands as JSON in place of assembly
+#[cfg(feature = "serde")]
+fn emit_ast<W: Write>(commands: &[Command], out: &mut W) -> Result<(), Box<dyn Error>> {
+    ::serde_json::to_writer_pretty(&mut *out, commands)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+fn emit_ast<W: Write>(_commands: &[Command], _out: &mut W) -> Result<(), Box<dyn Error>> {
+    Err(Box::new(InvalidArgError(String::from("--emit-ast"))))
+}
+
+//Returns the assembly along with the RAM address of every fixed symbol, statics included
+pub fn translate_str(
+    source: &str,
+    class_name: &str,
+    write_init: bool,
+) -> Result<(String, BTreeMap<String, u16>), Box<dyn Error>> {
+    let tokenizer = Tokenizer::from(default_ruleset());
+    let mut tokens: Vec<TokenList> = vec![];
+    let lines = strip_block_comments(source.lines().map(String::from).collect(), tokenizer.comment_leads());
+    for (i, line) in lines.iter().enumerate() {
+        tokens.push(tokenizer.tokenize(line).map_err(|err| err.located(class_name, i + 1))?);
+    }
+
+    let mut st: SymbolTable = SymbolTable::new();
+    st.load_starting_table();
+    let mut writer: AsmWriter = AsmWriter::from(st);
+    let mut parser = Parser::from(tokens, String::from(class_name));
+
+    let mut out: Vec<String> = vec![];
+    if write_init {
+        out.push(writer.write_init()?);
+    }
+    while parser.has_more_commands() {
+        if let Some(comm) = parser.advance()? {
+            out.push(writer.write_command(comm)?);
+        }
+    }
+    out.push(writer.write_routines());
+
+    let addresses = writer
+        .symbol_table()
+        .entries()
+        .filter_map(|(symbol, address)| match address {
+            Address::Absolute(addr) => Some((String::from(symbol), *addr)),
+            Address::Relative(_) => None,
+        })
+        .collect();
+    Ok((out.join(""), addresses))
+}
+
+fn default_outfile(path: &Path) -> PathBuf {
+    //Directories follow the nand2tetris convention of Dir/Dir.asm
+    if path.is_dir() {
+        let dir_name = path
+            .canonicalize()
+            .ok()
+            .and_then(|full| full.file_name().map(|name| name.to_os_string()));
+        if let Some(name) = dir_name {
+            return path.join(name).with_extension("asm");
+        }
+    }
+    uncompressed(path).with_extension("asm")
+}
+
+fn info(verbosity: Verbosity, message: &str) {
+    //Informational output is only shown with --verbose so the tool stays quiet in scripts.
+    //It goes to stderr so it never mixes with assembly written to stdout.
+    if verbosity == Verbosity::Verbose {
+        eprintln!("{}", message);
+    }
+}
+
+fn write_asm_file<W: Write>(config: &Config, mut out: W) -> Result<RunReport, Box<dyn Error>> {
+    let report = translate(config, &mut out)?;
+    out.flush()?;
+    Ok(report)
+}
+
+fn get_vmfiles_in_path(path: PathBuf, recurse: bool) -> IOResult<Vec<PathBuf>> {
+    let mut out: Vec<PathBuf> = vec![];
+    collect_vmfiles(&path, recurse, &mut out)?;
+    //read_dir order is platform dependent, so sort by class name for stable output
+    out.sort_by(|a, b| (a.file_stem(), a).cmp(&(b.file_stem(), b)));
+    Ok(out)
+}
+
+fn collect_vmfiles(path: &PathBuf, recurse: bool, out: &mut Vec<PathBuf>) -> IOResult<()> {
+    let dir_res = fs::read_dir(path)?
+        .map(|result| result.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+
+    for path in dir_res {
+        if path.is_dir() {
+            if recurse {
+                collect_vmfiles(&path, recurse, out)?;
+            }
+        } else if is_vmfile(&path) {
+            out.push(path.clone());
+        }
+    }
+    Ok(())
+}
+
+//A quoted pattern like Math*.vm reaches us unexpanded. Only the file name may contain
+//wildcards: * matches any run of characters and ? matches exactly one
+fn is_glob(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().contains(&['*', '?'][..]))
+}
+
+fn glob_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn glob_vmfiles(path: &Path) -> IOResult<Vec<PathBuf>> {
+    let pattern = path.file_name().unwrap().to_string_lossy().into_owned();
+    let mut out: Vec<PathBuf> = vec![];
+    for entry in fs::read_dir(glob_dir(path))? {
+        let file = entry?.path();
+        let matched = file
+            .file_name()
+            .is_some_and(|name| glob_match(pattern.as_bytes(), name.to_string_lossy().as_bytes()));
+        if matched && !file.is_dir() && is_vmfile(&file) {
+            out.push(file);
+        }
+    }
+    out.sort_by(|a, b| (a.file_stem(), a).cmp(&(b.file_stem(), b)));
+    Ok(out)
+}
+
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => glob_match(&pattern[1..], name) || (!name.is_empty() && glob_match(pattern, &name[1..])),
+        (Some(b'?'), Some(_)) => glob_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => glob_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+fn is_vmfile(path: &Path) -> bool {
+    has_extension(path, "vm") || (cfg!(feature = "gzip") && is_gzip(path))
+}
+
+fn is_gzip(path: &Path) -> bool {
+    has_extension(path, "gz") && has_extension(&path.with_extension(""), "vm")
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    //Case-insensitive filesystems may hand us Main.VM
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => ext.eq_ignore_ascii_case(extension),
+        None => false,
+    }
+}
+
+//Main.vm.gz names its class and outputs as Main.vm would
+fn uncompressed(path: &Path) -> PathBuf {
+    if is_gzip(path) {
+        path.with_extension("")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+#[derive(Debug)]
+struct FileTypeError;
+
+impl fmt::Display for FileTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Please provide a .vm file or directory")
+    }
+}
+
+impl Error for FileTypeError {}
+
+#[derive(Debug)]
+struct NoVmFilesError(PathBuf);
+
+impl fmt::Display for NoVmFilesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no .vm files found in {}", self.0.display())
+    }
+}
+
+impl Error for NoVmFilesError {}
+
+#[derive(Debug)]
+struct DuplicateClassError {
+    class_name: String,
+    first: PathBuf,
+    second: PathBuf,
+}
+
+impl fmt::Display for DuplicateClassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} and {} both have the class name {}",
+            self.first.display(),
+            self.second.display(),
+            self.class_name
+        )
+    }
+}
+
+impl Error for DuplicateClassError {}
+
+#[derive(Debug)]
+struct UnknownClassError(String);
+
+impl fmt::Display for UnknownClassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no input file has the class name {}", self.0)
+    }
+}
+
+impl Error for UnknownClassError {}
+
+//Returned by Config::new for --help and --version. The message goes to stdout and the
+//program exits successfully.
+#[derive(Debug)]
+pub struct EarlyExit {
+    pub message: String,
+}
+
+impl EarlyExit {
+    const FLAGS: &'static [&'static str] = &["--help", "-h", "--version", "-V"];
+
+    fn from_flag(flag: &str) -> EarlyExit {
+        let message = match flag {
+            "--version" | "-V" => format!("rusthackvm {}", env!("CARGO_PKG_VERSION")),
+            _ => String::from(USAGE),
+        };
+        EarlyExit { message }
+    }
+}
+
+impl fmt::Display for EarlyExit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for EarlyExit {}
+
+const USAGE: &str = "Usage: rusthackvm <file.vm | directory | pattern | ->... [options]
+
+Options:
+  -o, --output <path>     Write the assembly to <path> (- for stdout)
+      --stdout            Write the assembly to stdout
+      --stdin-name <name> Class name for VM code read from stdin
+      --class-name <file=name>
+                          Class name for the statics of file.vm, instead of its file name
+      --init              Write the bootstrap code (the default)
+      --no-init           Don't write the bootstrap code
+      --force-init        Write the bootstrap even without a Sys.init function
+      --sp-base <n>       Initial stack pointer for the bootstrap (default 256)
+      --debug             Step through the program in the interpreter
+      --sourcemap <path>  Write a TSV mapping .asm line ranges to VM commands
+      --stats             Print file, command and code size counts when done
+      --rules <path>      Add the token rules in <path> to the default ones
+      --max-count <n>     Highest local or argument count a function or call may use (default 32)
+      --hack              Also assemble the output into a .hack file
+      --symbols <path>    With --hack, list each label's ROM and variable's RAM address
+      --no-comments       Don't echo VM source lines as comments
+      --no-headers        Don't write a //Command #n line before each command
+      --comment-base <0|1>
+                          Number the //Command #n lines from 0 or 1 (default 0)
+      --registers <symbolic|numeric>
+                          Write registers as @R13 and @SP, or as their addresses
+      --target <emulator|strict>
+                          Strict drops comments and names no registers (default emulator)
+  -O, --optimize          Run the peephole optimizer
+      --inline-comparisons
+                          Expand eq/gt/lt in place instead of calling a shared routine
+      --share-comparisons With inline comparisons, expand each kind once per file
+      --no-recurse        Only read .vm files at the top of a directory
+      --emit-ast          Write the parsed commands as JSON (needs the serde feature)
+      --emit-tokens       Write each line's tokens instead of assembly
+      --check             Translate without writing anything, reporting any error
+      --separate          Write each .vm file to its own .asm beside it
+  -v, --verbose           Log progress to stderr
+  -q, --quiet             Only report errors
+  -h, --help              Show this message
+  -V, --version           Show the version";
+
+#[derive(Debug)]
+//Holds the argument that was rejected, or the option that conflicts with the others
+struct InvalidArgError(String);
+
+impl fmt::Display for InvalidArgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid command or option: {}", self.0)
+    }
+}
+
+impl Error for InvalidArgError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+    use tokenizer::TokenType;
+    use std::path::Path;
+
+    fn write_fixture(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = dir.join(name);
+        let mut f = fs::File::create(&path).unwrap();
+        f.write_all(contents.as_bytes()).unwrap();
+        path
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| String::from(*arg)).collect()
+    }
+
+    #[test]
+    fn invalid_arg_names_the_argument() {
+        let unknown = Config::new(args(&["rusthackvm", "Main.vm", "--foo"]).into_iter()).unwrap_err();
+        let missing = Config::new(args(&["rusthackvm", "Main.vm", "--sp-base"]).into_iter()).unwrap_err();
+        let conflict = Config::new(args(&["rusthackvm", "-", "--debug"]).into_iter()).unwrap_err();
+        assert_eq!(unknown.to_string(), "Invalid command or option: --foo");
+        assert_eq!(missing.to_string(), "Invalid command or option: --sp-base");
+        assert_eq!(conflict.to_string(), "Invalid command or option: --debug");
+    }
+
+    #[test]
+    fn config_parses_verbosity() {
+        let default = Config::new(args(&["rusthackvm", "Main.vm"]).into_iter()).unwrap();
+        let verbose = Config::new(args(&["rusthackvm", "Main.vm", "--verbose"]).into_iter()).unwrap();
+        let short = Config::new(args(&["rusthackvm", "Main.vm", "-v"]).into_iter()).unwrap();
+        let quiet = Config::new(args(&["rusthackvm", "Main.vm", "-v", "-q"]).into_iter()).unwrap();
+        assert_eq!(default.verbosity, Verbosity::Quiet);
+        assert_eq!(verbose.verbosity, Verbosity::Verbose);
+        assert_eq!(short.verbosity, Verbosity::Verbose);
+        assert_eq!(quiet.verbosity, Verbosity::Quiet);
+    }
+
+    #[test]
+    fn config_parses_init_anywhere() {
+        let default = Config::new(args(&["rusthackvm", "Main.vm"]).into_iter()).unwrap();
+        let before = Config::new(args(&["rusthackvm", "--no-init", "Main.vm"]).into_iter()).unwrap();
+        let after = Config::new(args(&["rusthackvm", "Main.vm", "-O", "--no-init"]).into_iter()).unwrap();
+        let explicit = Config::new(args(&["rusthackvm", "--init", "Main.vm"]).into_iter()).unwrap();
+        let last = Config::new(args(&["rusthackvm", "--no-init", "Main.vm", "--init"]).into_iter()).unwrap();
+        assert!(default.write_init);
+        assert!(!before.write_init);
+        assert!(!after.write_init);
+        assert!(explicit.write_init);
+        assert!(last.write_init);
+    }
+
+    #[test]
+    fn config_parses_optimize() {
+        let default = Config::new(args(&["rusthackvm", "Main.vm"]).into_iter()).unwrap();
+        let optimized = Config::new(args(&["rusthackvm", "Main.vm", "-O"]).into_iter()).unwrap();
+        assert!(!default.optimize);
+        assert!(optimized.optimize);
+    }
+
+    #[test]
+    fn config_output_path() {
+        let explicit = Config::new(args(&["rusthackvm", "Main.vm", "-o", "build/out.asm"]).into_iter()).unwrap();
+        let long = Config::new(args(&["rusthackvm", "Main.vm", "--output", "out.asm"]).into_iter()).unwrap();
+        let single = Config::new(args(&["rusthackvm", "src/Main.vm"]).into_iter()).unwrap();
+        assert_eq!(explicit.outfile, PathBuf::from("build/out.asm"));
+        assert_eq!(long.outfile, PathBuf::from("out.asm"));
+        assert_eq!(single.outfile, PathBuf::from("src/Main.asm"));
+        assert!(Config::new(args(&["rusthackvm", "Main.vm", "-o"]).into_iter()).is_err());
+    }
+
+    #[test]
+    fn config_directory_output_path() {
+        let dir = env::temp_dir().join("rusthackvm_config_directory_output_path").join("Project");
+        fs::create_dir_all(&dir).unwrap();
+        write_fixture(&dir, "Main.vm", "push constant 1\n");
+        let config = Config::new(args(&["rusthackvm", dir.to_str().unwrap()]).into_iter()).unwrap();
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+        assert_eq!(config.outfile, dir.join("Project.asm"));
+    }
+
+    #[test]
+    fn config_stdin() {
+        let config = Config::new(args(&["rusthackvm", "-"]).into_iter()).unwrap();
+        let named = Config::new(args(&["rusthackvm", "-", "--stdin-name", "Main", "-o", "Main.asm"]).into_iter()).unwrap();
+        assert!(config.read_stdin);
+        assert!(config.filevec.is_empty());
+        assert_eq!(config.outfile, PathBuf::from("-"));
+        assert_eq!(config.stdin_name, "Stdin");
+        assert_eq!(named.stdin_name, "Main");
+        assert_eq!(named.outfile, PathBuf::from("Main.asm"));
+    }
+
+    #[test]
+    fn translate_from_reader() {
+        let config = Config::new(args(&["rusthackvm", "-", "--no-init"]).into_iter()).unwrap();
+        let input = io::Cursor::new("push constant 3\npop static 0\n");
+        let tokenizer = new_tokenizer(&config).unwrap();
+        let cl = read_commands(&config, &tokenizer, &config.stdin_name, input).unwrap();
+
+        let mut out: Vec<u8> = vec![];
+        write_program(&config, vec![(config.stdin_name.clone(), cl)], &mut out).unwrap();
+        let asm = String::from_utf8(out).unwrap();
+        assert!(asm.starts_with("//Command #0\n// push constant 3\n@3\nD=A\n"));
+        assert!(asm.ends_with("@16\nM=D\n"));
+    }
+
+    #[test]
+    fn emit_tokens_dump() {
+        let config = Config::new(args(&["rusthackvm", "-", "--emit-tokens"]).into_iter()).unwrap();
+        let tokenizer = new_tokenizer(&config).unwrap();
+        let source = io::Cursor::new("push local 2\n\npop l@cal\n");
+
+        let mut out: Vec<u8> = vec![];
+        write_tokens("Main", &tokenizer, source, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Main:1: push Push true, local Symbol false, 2 Index false\nMain:3: pop Pop true,  Undefined false\n"
+        );
+    }
+
+    #[test]
+    fn errors_report_file_lines() {
+        let config = Config::new(args(&["rusthackvm", "-", "--no-init"]).into_iter()).unwrap();
+        let source = "// Header comment\n\n// push constant 1\npush constant 1\n  \npop local\n";
+        let tokenizer = new_tokenizer(&config).unwrap();
+
+        let err = read_commands(&config, &tokenizer, "Main", io::Cursor::new(source)).unwrap_err();
+        assert_eq!(err.to_string(), "Main: Improper arguments for Memory Access command at line 6");
+    }
+
+    #[test]
+    fn undefined_targets_name_file_and_line() {
+        let dir = env::temp_dir().join("rusthackvm_undefined_targets_name_file_and_line");
+        fs::create_dir_all(&dir).unwrap();
+        write_fixture(&dir, "Main.vm", "function Main.main 0\npush constant 1\ngoto NOPE\n");
+        let config = Config::new(args(&["rusthackvm", dir.to_str().unwrap(), "-o", "-"]).into_iter()).unwrap();
+        let label = translate(&config, &mut io::sink()).unwrap_err();
+
+        //The line is counted within Sys.vm, not across the whole program
+        write_fixture(&dir, "Main.vm", "function Main.main 0\npush constant 1\nreturn\n");
+        write_fixture(&dir, "Sys.vm", "function Sys.init 0\n\ncall Main.nope 0\n");
+        let config = Config::new(args(&["rusthackvm", dir.to_str().unwrap(), "-o", "-"]).into_iter()).unwrap();
+        let function = translate(&config, &mut io::sink()).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(label.to_string(), "Main: Jump to undefined label Main.main$NOPE at line 3");
+        assert_eq!(function.to_string(), "Sys: Call to undefined function Main.nope at line 3");
+    }
+
+    #[test]
+    fn undefined_tokens_name_file_and_line() {
+        let dir = env::temp_dir().join("rusthackvm_undefined_tokens_name_file_and_line");
+        fs::create_dir_all(&dir).unwrap();
+        write_fixture(&dir, "Main.vm", "function Main.main 0\npush constant 1\npop l@cal 0\n");
+        write_fixture(&dir, "Sys.vm", "function Sys.init 0\ncall Main.main 0\n");
+        let config = Config::new(args(&["rusthackvm", dir.to_str().unwrap(), "-o", "-"]).into_iter()).unwrap();
+        let err = translate(&config, &mut io::sink()).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(err.to_string(), "Main: Unrecognized token l@cal at line 3");
+
+        //Lines after a bad one keep their numbers
+        let tokenizer = Tokenizer::from(default_ruleset());
+        let mut commands = CommandReader::new(io::Cursor::new("l@cal\npush local 0\n"), String::from("Main"), &tokenizer);
+        assert!(commands.next().unwrap().is_err());
+        assert_eq!(commands.next().unwrap().unwrap().line, 2);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_read_matches_sequential() {
+        use rayon::prelude::*;
+        let dir = env::temp_dir().join("rusthackvm_parallel_read_matches_sequential");
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..16 {
+            let source = format!("function Class{0}.f 0\npush constant {0}\npop static {0}\nreturn\n", i);
+            write_fixture(&dir, &format!("Class{}.vm", i), &source);
+        }
+        let config = Config::new(args(&["rusthackvm", dir.to_str().unwrap()]).into_iter()).unwrap();
+        let tokenizer = new_tokenizer(&config).unwrap();
+
+        let read = |filename: &PathBuf| read_file(&config, &tokenizer, filename).unwrap();
+        let parallel: Vec<(String, Vec<SourceCommand>)> = config.filevec.par_iter().map(read).collect();
+        let sequential: Vec<(String, Vec<SourceCommand>)> = config.filevec.iter().map(read).collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(parallel.len(), 16);
+        assert_eq!(parallel, sequential);
+    }
+
+    #[test]
+    fn streaming_matches_buffered() {
+        let dir = env::temp_dir().join("rusthackvm_streaming_matches_buffered");
+        fs::create_dir_all(&dir).unwrap();
+        let source = "function Main.main 1 # locals\n/* spans\nlines */ push constant -3\npop static 0\nlabel LOOP\npush static 0\nlt\nif-goto LOOP\nreturn\n";
+        write_fixture(&dir, "Main.vm", source);
+        let config = Config::new(args(&["rusthackvm", dir.to_str().unwrap(), "--no-init", "--no-comments"]).into_iter()).unwrap();
+
+        let mut streamed: Vec<u8> = vec![];
+        translate(&config, &mut streamed).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let (buffered, _) = translate_str(source, "Main", false).unwrap();
+        assert!(!streamed.is_empty());
+        assert_eq!(String::from_utf8(streamed).unwrap(), buffered);
+    }
+
+    #[test]
+    fn write_asm_to_stdout_sink() {
+        let dir = env::temp_dir().join("rusthackvm_write_asm_to_stdout_sink");
+        fs::create_dir_all(&dir).unwrap();
+        let main = write_fixture(&dir, "Main.vm", "push constant 2\n");
+        let config = Config::new(args(&["rusthackvm", main.to_str().unwrap(), "--no-init", "--stdout", "--no-comments"]).into_iter()).unwrap();
+        let piped = Config::new(args(&["rusthackvm", main.to_str().unwrap(), "-o", "-"]).into_iter()).unwrap();
+
+        let mut out: Vec<u8> = vec![];
+        write_asm_file(&config, &mut out).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(config.outfile, PathBuf::from("-"));
+        assert_eq!(piped.outfile, PathBuf::from("-"));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "//Command #0\n@2\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1\n"
+        );
+    }
+
+    #[test]
+    fn vmfiles_are_sorted() {
+        let dir = env::temp_dir().join("rusthackvm_vmfiles_are_sorted");
+        fs::create_dir_all(&dir).unwrap();
+        for name in &["Sys.vm", "Main.vm", "Ball.vm", "notes.txt", "Board.vm"] {
+            write_fixture(&dir, name, "");
+        }
+
+        let files = get_vmfiles_in_path(dir.clone(), true).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<&str> = files.iter().map(|f| f.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, vec!["Ball.vm", "Board.vm", "Main.vm", "Sys.vm"]);
+    }
+
+    #[test]
+    fn vmfiles_in_nested_directories() {
+        let dir = env::temp_dir().join("rusthackvm_vmfiles_in_nested_directories");
+        fs::create_dir_all(dir.join("lib").join("util")).unwrap();
+        write_fixture(&dir, "Main.vm", "");
+        write_fixture(&dir.join("lib"), "List.vm", "");
+        write_fixture(&dir.join("lib").join("util"), "Array.vm", "");
+
+        let deep = get_vmfiles_in_path(dir.clone(), true).unwrap();
+        let shallow = Config::new(args(&["rusthackvm", dir.to_str().unwrap(), "--no-recurse"]).into_iter()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<&str> = deep.iter().map(|f| f.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, vec!["Array.vm", "List.vm", "Main.vm"]);
+        assert!(!shallow.recurse);
+        assert_eq!(shallow.filevec, vec![dir.join("Main.vm")]);
+    }
+
+    #[test]
+    fn duplicate_class_names_are_an_error() {
+        let dir = env::temp_dir().join("rusthackvm_duplicate_class_names_are_an_error");
+        fs::create_dir_all(dir.join("a")).unwrap();
+        fs::create_dir_all(dir.join("b")).unwrap();
+        let first = write_fixture(&dir.join("a"), "Main.vm", "function Main.one 0\npush constant 1\nreturn\n");
+        let second = write_fixture(&dir.join("b"), "Main.vm", "function Main.two 0\npush constant 2\nreturn\n");
+
+        let nested = Config::new(args(&["rusthackvm", dir.to_str().unwrap()]).into_iter());
+        let stdin = Config::new(args(&["rusthackvm", "-", first.to_str().unwrap(), "--stdin-name", "Main"]).into_iter());
+        let renamed = Config::new(
+            args(&["rusthackvm", first.to_str().unwrap(), "--class-name", "Main=Other", "--stdin-name", "Other", "-"])
+                .into_iter(),
+        );
+        let shallow = Config::new(args(&["rusthackvm", dir.join("a").to_str().unwrap()]).into_iter());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            nested.unwrap_err().to_string(),
+            format!("{} and {} both have the class name Main", first.display(), second.display())
+        );
+        assert_eq!(stdin.unwrap_err().to_string(), format!("- and {} both have the class name Main", first.display()));
+        assert!(renamed.is_err());
+        assert!(shallow.is_ok());
+    }
+
+    #[test]
+    fn empty_directory_is_an_error() {
+        let dir = env::temp_dir().join("rusthackvm_empty_directory_is_an_error");
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        write_fixture(&dir, "notes.txt", "");
+        write_fixture(&dir.join("lib"), "List.vm", "");
+
+        let empty = Config::new(args(&["rusthackvm", dir.to_str().unwrap(), "--no-recurse"]).into_iter());
+        let nested = Config::new(args(&["rusthackvm", dir.to_str().unwrap()]).into_iter());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(empty.unwrap_err().to_string(), format!("no .vm files found in {}", dir.display()));
+        assert!(nested.is_ok());
+    }
+
+    #[test]
+    fn uppercase_extension_accepted() {
+        let dir = env::temp_dir().join("rusthackvm_uppercase_extension_accepted");
+        fs::create_dir_all(&dir).unwrap();
+        let foo = write_fixture(&dir, "Foo.VM", "");
+        write_fixture(&dir, "Bar.Vm", "");
+
+        let single = Config::new(args(&["rusthackvm", foo.to_str().unwrap()]).into_iter()).unwrap();
+        let found = get_vmfiles_in_path(dir.clone(), false).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(single.filevec, vec![foo.clone()]);
+        assert_eq!(single.outfile, dir.join("Foo.asm"));
+        assert_eq!(found, vec![dir.join("Bar.Vm"), foo]);
+    }
+
+    #[test]
+    fn builder_file_and_directory() {
+        let dir = env::temp_dir().join("rusthackvm_builder_file_and_directory");
+        fs::create_dir_all(&dir).unwrap();
+        let main = write_fixture(&dir, "Main.vm", "");
+        let notes = write_fixture(&dir, "notes.txt", "");
+        write_fixture(&dir, "Sys.vm", "");
+
+        let file = Config::builder()
+            .input(main.clone())
+            .output(dir.join("out.asm"))
+            .write_init(false)
+            .verbose(Verbosity::Verbose)
+            .build()
+            .unwrap();
+        let directory = Config::builder().input(dir.clone()).build().unwrap();
+        let wrong_type = Config::builder().input(notes).build();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(file.filevec, vec![main.clone()]);
+        assert_eq!(file.outfile, dir.join("out.asm"));
+        assert!(!file.write_init);
+        assert_eq!(file.verbosity, Verbosity::Verbose);
+        assert_eq!(directory.filevec, vec![main, dir.join("Sys.vm")]);
+        assert!(directory.write_init);
+        assert!(wrong_type.is_err());
+        assert!(Config::builder().build().is_err());
+    }
+
+    #[test]
+    fn help_and_version_exit_early() {
+        let help = Config::new(args(&["rusthackvm", "--help"]).into_iter()).unwrap_err();
+        let missing = Config::new(args(&["rusthackvm", "Missing.vm", "-h"]).into_iter()).unwrap_err();
+        let version = Config::new(args(&["rusthackvm", "-V"]).into_iter()).unwrap_err();
+
+        assert!(help.downcast_ref::<EarlyExit>().unwrap().message.starts_with("Usage:"));
+        assert!(missing.downcast_ref::<EarlyExit>().is_some());
+        assert_eq!(
+            version.downcast_ref::<EarlyExit>().unwrap().message,
+            format!("rusthackvm {}", env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    #[test]
+    fn glob_selects_matching_files() {
+        let dir = env::temp_dir().join("rusthackvm_glob_selects_matching_files");
+        fs::create_dir_all(&dir).unwrap();
+        for name in &["Main.vm", "Math.vm", "Memory.vm", "Sys.vm", "Mnotes.txt"] {
+            write_fixture(&dir, name, "push constant 1\n");
+        }
+        let pattern = dir.join("M*.vm");
+        let config = Config::new(args(&["rusthackvm", pattern.to_str().unwrap()]).into_iter());
+        let single = Config::new(args(&["rusthackvm", dir.join("Ma?n.vm").to_str().unwrap()]).into_iter());
+        let none = Config::new(args(&["rusthackvm", dir.join("X*.vm").to_str().unwrap()]).into_iter());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.filevec, vec![dir.join("Main.vm"), dir.join("Math.vm"), dir.join("Memory.vm")]);
+        assert_eq!(config.outfile.extension().unwrap(), "asm");
+        assert_eq!(config.outfile.parent().unwrap(), dir.as_path());
+        assert_eq!(single.unwrap().filevec, vec![dir.join("Main.vm")]);
+        assert!(none.is_err());
+    }
+
+    #[test]
+    fn multiple_inputs_are_merged() {
+        let dir = env::temp_dir().join("rusthackvm_multiple_inputs_are_merged");
+        let lib = dir.join("Lib");
+        fs::create_dir_all(&lib).unwrap();
+        let main = write_fixture(&dir, "Main.vm", "push constant 1\n");
+        let sys = write_fixture(&dir, "Sys.vm", "push constant 2\n");
+        let math = write_fixture(&lib, "Math.vm", "push constant 3\n");
+        let array = write_fixture(&lib, "Array.vm", "push constant 4\n");
+        let paths = [main.to_str().unwrap(), "--no-init", sys.to_str().unwrap(), lib.to_str().unwrap()];
+        let config = Config::new(args(&["rusthackvm", paths[0], paths[1], paths[2], paths[3]]).into_iter());
+        let repeated = Config::new(args(&["rusthackvm", lib.to_str().unwrap(), array.to_str().unwrap()]).into_iter());
+        let named = Config::new(args(&["rusthackvm", "-o", "out.asm", paths[2], paths[0]]).into_iter());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.filevec, vec![main.clone(), sys.clone(), array.clone(), math]);
+        assert_eq!(config.outfile, dir.join("Main.asm"));
+        assert!(!config.write_init);
+        assert_eq!(repeated.unwrap().filevec.len(), 2);
+        let named = named.unwrap();
+        assert_eq!(named.outfile, PathBuf::from("out.asm"));
+        assert_eq!(named.filevec, vec![sys, main]);
+    }
+
+    #[test]
+    fn check_writes_nothing() {
+        let dir = env::temp_dir().join("rusthackvm_check_writes_nothing");
+        fs::create_dir_all(&dir).unwrap();
+        let good = write_fixture(&dir, "Good.vm", "push constant 1\npop local 0\n");
+        let bad = write_fixture(&dir, "Bad.vm", "push constant 1\npop local\n");
+
+        let good_config = Config::new(args(&["rusthackvm", good.to_str().unwrap(), "--check"]).into_iter()).unwrap();
+        let bad_config = Config::new(args(&["rusthackvm", bad.to_str().unwrap(), "--check"]).into_iter()).unwrap();
+        let good_result = run(good_config);
+        let bad_result = run(bad_config);
+        let wrote_output = dir.join("Good.asm").exists() || dir.join("Bad.asm").exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(good_result.is_ok());
+        assert_eq!(
+            bad_result.unwrap_err().to_string(),
+            "Bad: Improper arguments for Memory Access command at line 2"
+        );
+        assert!(!wrote_output);
+    }
+
+    #[test]
+    fn sourcemap_points_at_commands() {
+        let dir = env::temp_dir().join("rusthackvm_sourcemap_points_at_commands");
+        fs::create_dir_all(&dir).unwrap();
+        let main = write_fixture(&dir, "Main.vm", "// two commands\npush constant 7\nnot\n");
+        let map_path = dir.join("Main.map");
+        let config = Config::new(
+            args(&["rusthackvm", main.to_str().unwrap(), "--sourcemap", map_path.to_str().unwrap()]).into_iter(),
+        )
+        .unwrap();
+        run(config).unwrap();
+        let asm = fs::read_to_string(dir.join("Main.asm")).unwrap();
+        let map = fs::read_to_string(&map_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let asm_lines: Vec<&str> = asm.lines().collect();
+        let rows: Vec<Vec<&str>> = map.lines().map(|row| row.split('\t').collect()).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(&rows[0][2..], &["Main.vm", "2", "push constant 7"]);
+        assert_eq!(&rows[1][2..], &["Main.vm", "3", "not"]);
+        for row in &rows {
+            let start: usize = row[0].parse().unwrap();
+            let end: usize = row[1].parse().unwrap();
+            assert_eq!(asm_lines[start], format!("// {}", row[4]));
+            assert!(end <= asm_lines.len());
+        }
+        assert_eq!(rows[1][0].parse::<usize>().unwrap(), rows[0][1].parse::<usize>().unwrap() + 1);
+        assert!(Config::new(args(&["rusthackvm", "-", "--sourcemap", "x.map", "-O"]).into_iter()).is_err());
+    }
+
+    #[test]
+    fn symbols_sidecar_lists_labels() {
+        let dir = env::temp_dir().join("rusthackvm_symbols_sidecar_lists_labels");
+        fs::create_dir_all(&dir).unwrap();
+        let main = write_fixture(&dir, "Main.vm", "function Main.main 0\nlabel LOOP\ngoto LOOP\n");
+        let sym_path = dir.join("Main.sym");
+        let config = Config::new(
+            args(&["rusthackvm", main.to_str().unwrap(), "--no-init", "--hack", "--symbols", sym_path.to_str().unwrap()])
+                .into_iter(),
+        )
+        .unwrap();
+        run(config).unwrap();
+        let sym = fs::read_to_string(&sym_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        //Main.main has no locals to set up, so the loop label lands on the same instruction
+        assert!(sym.starts_with("ROM\tMain.main\t0\nROM\tMain.main$LOOP\t0\n"));
+        assert!(Config::new(args(&["rusthackvm", "Main.vm", "--symbols", "Main.sym"]).into_iter()).is_err());
+    }
+
+    #[test]
+    fn class_name_override_qualifies_statics() {
+        let dir = env::temp_dir().join("rusthackvm_class_name_override_qualifies_statics");
+        fs::create_dir_all(&dir).unwrap();
+        let generated = write_fixture(&dir, "gen_0042.vm", "push constant 1\npop static 3\n");
+        let path = generated.to_str().unwrap();
+        let config = Config::new(args(&["rusthackvm", path, "--class-name", "gen_0042=Main"]).into_iter()).unwrap();
+        let files = read_sources(&config).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(files[0].0, "Main");
+        let mut writer = new_writer(&config);
+        for command in &files[0].1 {
+            writer.emit(&command.command).unwrap();
+        }
+        assert!(writer.symbol_table().contains("Main.3"));
+        assert!(!writer.symbol_table().contains("gen_0042.3"));
+        let missing = Config::new(args(&["rusthackvm", "-", "--class-name", "Main"]).into_iter()).unwrap_err();
+        assert_eq!(missing.to_string(), "Invalid command or option: --class-name");
+    }
+
+    #[test]
+    fn rules_file_extends_tokenizer() {
+        let dir = env::temp_dir().join("rusthackvm_rules_file_extends_tokenizer");
+        fs::create_dir_all(&dir).unwrap();
+        let main = write_fixture(&dir, "Main.vm", "push constant 5\npush constant 3\nminus\n");
+        let rules = write_fixture(&dir, "dialect.rules", "# sub under another name\nSubtract ^minus$ true\n");
+        let plain = Config::new(args(&["rusthackvm", main.to_str().unwrap(), "--no-comments"]).into_iter()).unwrap();
+        let extended = Config::new(
+            args(&["rusthackvm", main.to_str().unwrap(), "--no-comments", "--rules", rules.to_str().unwrap()]).into_iter(),
+        )
+        .unwrap();
+
+        let mut out: Vec<u8> = vec![];
+        let unknown = translate(&plain, &mut out).unwrap_err();
+        translate(&extended, &mut out).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(unknown.to_string(), "Main: Expected keyword at line 3");
+        assert!(String::from_utf8(out).unwrap().contains("//Command #2\n@SP\nAM=M-1\nD=M\n@SP\nAM=M-1\nD=M-D\n"));
+    }
+
+    #[test]
+    fn targets_differ_in_comments_and_registers() {
+        let dir = env::temp_dir().join("rusthackvm_targets_differ");
+        fs::create_dir_all(&dir).unwrap();
+        let main = write_fixture(&dir, "Main.vm", "push constant 7\npop temp 0\n");
+        let emulator = Config::new(args(&["rusthackvm", main.to_str().unwrap(), "--target", "emulator"]).into_iter()).unwrap();
+        let strict = Config::new(args(&["rusthackvm", main.to_str().unwrap(), "--target", "strict"]).into_iter()).unwrap();
+
+        let mut emulator_out: Vec<u8> = vec![];
+        let mut strict_out: Vec<u8> = vec![];
+        translate(&emulator, &mut emulator_out).unwrap();
+        translate(&strict, &mut strict_out).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let emulator_out = String::from_utf8(emulator_out).unwrap();
+        let strict_out = String::from_utf8(strict_out).unwrap();
+        assert!(emulator_out.starts_with("//Command #0\n// push constant 7\n@7\nD=A\n@SP\n"));
+        assert!(strict_out.starts_with("@7\nD=A\n@0\n"));
+        assert!(!strict_out.contains("//") && !strict_out.contains("@SP\n"));
+        assert!(Config::new(args(&["rusthackvm", "Main.vm", "--target", "hardware"]).into_iter()).is_err());
+    }
+
+    #[test]
+    fn run_reports_counts() {
+        let dir = env::temp_dir().join("rusthackvm_run_reports_counts");
+        fs::create_dir_all(&dir).unwrap();
+        write_fixture(&dir, "Sys.vm", "// entry point\nfunction Sys.init 0\ncall Main.main 0\nreturn\n");
+        write_fixture(&dir, "Main.vm", "function Main.main 0\n\npush constant 1\nreturn\n");
+        let config = Config::new(args(&["rusthackvm", dir.to_str().unwrap()]).into_iter()).unwrap();
+        let outfile = config.outfile.clone();
+        let report = run_with_report(config).unwrap();
+        let size = fs::metadata(&outfile).unwrap().len() as usize;
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(report.files, 2);
+        assert_eq!(report.commands, 6);
+        assert_eq!(report.bytes_written, size);
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn run_reports_unreachable_warning() {
+        let dir = env::temp_dir().join("rusthackvm_run_reports_unreachable_warning");
+        fs::create_dir_all(&dir).unwrap();
+        write_fixture(&dir, "Main.vm", "function Main.main 0\npush constant 1\nreturn\n");
+        write_fixture(&dir, "Sys.vm", "function Sys.init 0\ncall Main.main 0\nreturn\npush constant 2\n");
+        let config = Config::new(args(&["rusthackvm", dir.to_str().unwrap(), "--no-init"]).into_iter()).unwrap();
+        let report = run_with_report(config).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let expected = Warning::Unreachable { file: String::from("Sys"), line: 4, source: String::from("push constant 2") };
+        assert_eq!(report.warnings, vec![expected.clone()]);
+        assert_eq!(expected.to_string(), "Warning: Sys: unreachable command at line 4: push constant 2");
+    }
+
+    #[test]
+    fn ram_segment_warns_on_reserved_registers() {
+        let dir = env::temp_dir().join("rusthackvm_ram_segment_warns_on_reserved_registers");
+        fs::create_dir_all(&dir).unwrap();
+        let main = write_fixture(&dir, "Main.vm", "push ram 17\npop ram 3\n");
+        let config = Config::new(args(&["rusthackvm", main.to_str().unwrap(), "--no-init"]).into_iter()).unwrap();
+        let outfile = config.outfile.clone();
+        let report = run_with_report(config).unwrap();
+        let out = fs::read_to_string(&outfile).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let warnings: Vec<String> = report.warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(warnings, vec!["Warning: Main: ram access to a reserved register at line 2: pop ram 3"]);
+        assert!(out.contains("@17\nA=M\n") && out.contains("@3\nM=D\n"));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzipped_source_matches_plain() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let dir = env::temp_dir().join("rusthackvm_gzipped_source_matches_plain");
+        fs::create_dir_all(dir.join("plain")).unwrap();
+        fs::create_dir_all(dir.join("gzip")).unwrap();
+        let source = "function Main.main 0\npush constant 7\npop static 0\npush static 0\nreturn\n";
+        let plain = write_fixture(&dir.join("plain"), "Main.vm", source);
+        let gzipped = dir.join("gzip").join("Main.vm.gz");
+        let mut encoder = GzEncoder::new(fs::File::create(&gzipped).unwrap(), Compression::default());
+        encoder.write_all(source.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let plain_config = Config::new(args(&["rusthackvm", plain.to_str().unwrap()]).into_iter()).unwrap();
+        let gzip_config = Config::new(args(&["rusthackvm", gzipped.to_str().unwrap()]).into_iter()).unwrap();
+        let from_dir = Config::new(args(&["rusthackvm", dir.join("gzip").to_str().unwrap()]).into_iter()).unwrap();
+        let mut plain_out: Vec<u8> = vec![];
+        let mut gzip_out: Vec<u8> = vec![];
+        translate(&plain_config, &mut plain_out).unwrap();
+        translate(&gzip_config, &mut gzip_out).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(gzip_config.outfile, dir.join("gzip").join("Main.asm"));
+        assert_eq!(from_dir.filevec, vec![gzipped]);
+        assert_eq!(String::from_utf8(gzip_out).unwrap(), String::from_utf8(plain_out).unwrap());
+    }
+
+    #[test]
+    fn separate_outputs_per_file() {
+        let dir = env::temp_dir().join("rusthackvm_separate_outputs_per_file");
+        fs::create_dir_all(&dir).unwrap();
+        write_fixture(&dir, "Main.vm", "function Main.main 0\npush constant 1\npush constant 2\neq\nreturn\n");
+        write_fixture(&dir, "Sys.vm", "function Sys.init 0\ncall Main.main 0\nlabel END\ngoto END\n");
+        let config = Config::new(args(&["rusthackvm", dir.to_str().unwrap(), "--separate"]).into_iter()).unwrap();
+
+        run(config).unwrap();
+        let main = fs::read_to_string(dir.join("Main.asm")).unwrap();
+        let sys = fs::read_to_string(dir.join("Sys.asm")).unwrap();
+        let combined = dir.join(dir.file_name().unwrap()).with_extension("asm").exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(main.starts_with("@256\nD=A\n@SP\nM=D\n"));
+        assert!(main.contains("(Main.main)\n"));
+        assert!(main.contains("(BRANCH0)\n"));
+        assert!(!main.contains("(Sys.init)"));
+        assert!(sys.starts_with("//Command #"));
+        assert!(sys.contains("(Sys.init)\n"));
+        assert!(sys.contains("(Sys.init$END)\n"));
+        //The bootstrap's return label was taken first, so Sys.init's call gets the next one
+        assert!(sys.contains("(Sys.init$ret.1)\n"));
+        assert!(!combined);
+    }
+
+    #[test]
+    fn separate_outputs_share_comparisons_per_file() {
+        let dir = env::temp_dir().join("rusthackvm_separate_outputs_share_comparisons_per_file");
+        fs::create_dir_all(&dir).unwrap();
+        let compare = "push constant 1\npush constant 2\neq\npush constant 3\neq\n";
+        write_fixture(&dir, "Main.vm", &format!("function Main.main 0\n{}return\n", compare));
+        write_fixture(&dir, "Sys.vm", &format!("function Sys.init 0\n{}call Main.main 0\nreturn\n", compare));
+        let config =
+            Config::new(args(&["rusthackvm", dir.to_str().unwrap(), "--separate", "--share-comparisons"]).into_iter())
+                .unwrap();
+
+        run(config).unwrap();
+        let main = fs::read_to_string(dir.join("Main.asm")).unwrap();
+        let sys = fs::read_to_string(dir.join("Sys.asm")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        //Each file has its own eq block, jumped to by its second eq
+        assert_eq!(main.matches("D;JEQ\n").count(), 1);
+        assert_eq!(sys.matches("D;JEQ\n").count(), 1);
+        assert!(main.contains("(COMPARE_EQ0)\n") && main.contains("@COMPARE_EQ0\n0;JMP\n"));
+        assert!(sys.contains("(COMPARE_EQ2)\n") && sys.contains("@COMPARE_EQ2\n0;JMP\n"));
+    }
+
+    fn read_map(config: &Config, files: &[(&str, &str)]) -> Vec<(String, Vec<SourceCommand>)> {
+        let tokenizer = new_tokenizer(config).unwrap();
+        let mut file_map = BTreeMap::new();
+        for (name, source) in files {
+            file_map.insert(String::from(*name), read_commands(config, &tokenizer, name, io::Cursor::new(*source)).unwrap());
+        }
+        file_map.into_iter().collect()
+    }
+
+    fn translate_map(config: &Config, files: &[(&str, &str)]) -> String {
+        let mut out: Vec<u8> = vec![];
+        write_program(config, read_map(config, files), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn stats_count_commands() {
+        let config = Config::new(args(&["rusthackvm", "-", "--stats", "--no-comments"]).into_iter()).unwrap();
+        let source = "push constant 7\npush constant 8\neq\npop local 0\nlabel END\ngoto END\n";
+        let files = read_map(&config, &[("Main", source)]);
+
+        let stats = Stats::from_files(&files);
+        let mut writer = new_writer(&config);
+        let mut out: Vec<u8> = vec![];
+        for (_, cl) in files {
+            write_commands(&config, &mut writer, cl, &mut out).unwrap();
+        }
+        out.extend(writer.write_routines().into_bytes());
+        let stats = stats.with_writer(&writer);
+
+        assert_eq!(stats.files, 1);
+        assert_eq!(stats.command_count(), 6);
+        assert_eq!(stats.commands["push"], 2);
+        assert_eq!(stats.commands["arithmetic"], 1);
+        assert_eq!(stats.branches, 1);
+        assert_eq!(stats.asm_lines, String::from_utf8(out).unwrap().lines().count());
+        assert!(stats.to_string().starts_with("Files: 1\nCommands: 6\n"));
+    }
+
+    #[test]
+    fn bootstrap_only_with_sys_init() {
+        let config = Config::new(args(&["rusthackvm", "-"]).into_iter()).unwrap();
+        let forced = Config::new(args(&["rusthackvm", "-", "--force-init"]).into_iter()).unwrap();
+        let simple = [("Main", "push constant 7\npush constant 8\nadd\n")];
+        let with_sys = [("Sys", "function Sys.init 0\nlabel END\ngoto END\n")];
+
+        assert!(translate_map(&config, &simple).starts_with("//Command #0\n"));
+        assert!(translate_map(&forced, &simple).starts_with("@256\nD=A\n@SP\nM=D\n"));
+        assert!(translate_map(&config, &with_sys).starts_with("@256\nD=A\n@SP\nM=D\n"));
+    }
+
+    #[test]
+    fn sp_base_option() {
+        let config = Config::new(args(&["rusthackvm", "-", "--force-init", "--sp-base", "512"]).into_iter()).unwrap();
+        let too_high = Config::new(args(&["rusthackvm", "-", "--sp-base", "16384"]).into_iter());
+        let not_a_number = Config::new(args(&["rusthackvm", "-", "--sp-base", "top"]).into_iter());
+
+        assert_eq!(config.sp_base, 512);
+        assert!(translate_map(&config, &[("Main", "add\n")]).starts_with("@512\nD=A\n@SP\nM=D\n"));
+        assert!(too_high.is_err());
+        assert!(not_a_number.is_err());
+    }
+
+    #[test]
+    fn debug_session_steps_and_prints() {
+        let commands = vec![
+            Command::Push { segment: String::from("constant"), index: 7, class_name: String::from("Main") },
+            Command::Push { segment: String::from("constant"), index: 8, class_name: String::from("Main") },
+            Command::Arithmetic(TokenType::Add),
+        ];
+        let mut vm = Vm::new(commands).unwrap();
+        let mut out: Vec<u8> = vec![];
+        debug_session(&mut vm, io::Cursor::new("s\nstep\np\nc\n"), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("0: push constant 7\n> "));
+        assert!(out.contains("2: add\n> SP=258 LCL=0 ARG=0 THIS=0 THAT=0\nStack top: [7, 8]\n"));
+        assert!(out.ends_with("Halted\nSP=257 LCL=0 ARG=0 THIS=0 THAT=0\nStack top: [15]\n"));
+    }
+
+    #[test]
+    fn translate_is_deterministic() {
+        let dir = env::temp_dir().join("rusthackvm_translate_is_deterministic");
+        fs::create_dir_all(&dir).unwrap();
+        let main = write_fixture(&dir, "Main.vm", "function Main.main 0\npush constant 1\nreturn\n");
+        let sys = write_fixture(&dir, "Sys.vm", "function Sys.init 0\ncall Main.main 0\nlabel END\ngoto END\n");
+        let config = Config::builder()
+            .input(sys)
+            .input(main)
+            .output(dir.join("out.asm"))
+            .verbose(Verbosity::Quiet)
+            .build()
+            .unwrap();
+
+        let mut first: Vec<u8> = vec![];
+        let mut second: Vec<u8> = vec![];
+        translate(&config, &mut first).unwrap();
+        translate(&config, &mut second).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let first = String::from_utf8(first).unwrap();
+        let second = String::from_utf8(second).unwrap();
+        assert_eq!(first, second);
+        assert!(first.starts_with("@256\nD=A\n@SP\nM=D\n"));
+        assert!(first.find("(Main.main)").unwrap() < first.find("(Sys.init)").unwrap());
+    }
+
+    #[test]
+    fn translate_str_arithmetic() {
+        let (out, _) = translate_str("push constant 7\npush constant 8\nadd\n", "Main", false).unwrap();
+        assert!(out.starts_with("//Command #0\n@7\nD=A\n"));
+        assert!(out.contains("//Command #1\n@8\nD=A\n"));
+        assert!(out.contains("//Command #2\n@SP\nAM=M-1\nD=M\n@SP\nAM=M-1\nD=D+M\n"));
+    }
+
+    #[test]
+    fn translate_str_shares_comparisons() {
+        let source = "push constant 1\npush constant 2\neq\npush constant 3\neq\ngt\n";
+        let (out, _) = translate_str(source, "Main", false).unwrap();
+        assert_eq!(out.matches("(COMPARE_EQ)\n").count(), 1);
+        assert_eq!(out.matches("@COMPARE_EQ\n0;JMP\n").count(), 2);
+        assert_eq!(out.matches("@COMPARE_GT\n0;JMP\n").count(), 1);
+        assert!(out.ends_with("(COMPARE_END)\n"));
+
+        let config = Config::new(args(&["rusthackvm", "Main.vm", "--inline-comparisons"]).into_iter()).unwrap();
+        assert!(config.inline_comparisons);
+    }
+
+    #[test]
+    fn translate_str_with_init() {
+        let (out, _) = translate_str("function Sys.init 0\n", "Sys", true).unwrap();
+        assert!(out.starts_with("@256\nD=A\n@SP\nM=D\n"));
+        assert!(out.contains("(Sys.init)\n"));
+    }
+
+    #[test]
+    fn translate_str_reports_static_addresses() {
+        let source = "push constant 1\npop static 3\npush constant 2\npop static 0\npush static 3\n";
+        let (out, addresses) = translate_str(source, "Main", false).unwrap();
+        assert_eq!(addresses["Main.3"], 16);
+        assert_eq!(addresses["Main.0"], 17);
+        assert_eq!(addresses["temp"], 5);
+        assert!(!addresses.contains_key("local"));
+        assert!(out.contains("@16\nM=D\n"));
+    }
+}
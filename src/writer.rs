@@ -0,0 +1,1590 @@
+use assembler::Assembler;
+use error::VmError;
+use parser::{Command, SourceCommand};
+use symbol_table::{Address, SymbolTable};
+use std::collections::HashMap;
+use std::io::Write;
+use tokenizer::TokenType;
+
+//The assembly lines (1-based, inclusive) written for one VM source command
+#[derive(Debug, PartialEq, Clone)]
+pub struct SourceMapEntry {
+    pub start: usize,
+    pub end: usize,
+    pub file: String,
+    pub line: usize,
+    pub command: Command,
+}
+
+//How A-instructions name the predefined registers. Mixed is the writer's own style: pointer
+//words by name (@SP, @R13) and temp by address (@5). Symbolic names temp as @R5 too, and
+//Numeric writes every register as its address (@0, @13)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RegisterNames {
+    Mixed,
+    Symbolic,
+    Numeric,
+}
+
+//The assembler the output is meant for. Emulator is what the course's CPU emulator and
+//assembler accept: // comments, and predefined symbols like SP and R13 in A-instructions.
+//Strict is for simpler assemblers that may not strip comments or know the predefined
+//symbols, so it writes no comment lines and every register as its numeric address
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Target {
+    Emulator,
+    Strict,
+}
+
+#[derive(Debug)]
+pub struct AsmWriter {
+    line_count: usize,
+    branch_count: usize,
+    call_count: usize,
+    symbol_table: SymbolTable,
+    current_function: Option<String>,
+    comments: bool,
+    headers: bool,
+    comment_base: u16,
+    register_names: RegisterNames,
+    inline_comparisons: bool,
+    comparison_called: bool,
+    share_comparisons: bool,
+    //The label of each comparison kind's block written so far in the current file
+    comparison_blocks: HashMap<String, String>,
+    sp_base: u16,
+    asm_lines: usize,
+    source_file: String,
+    source_map: Option<Vec<SourceMapEntry>>,
+}
+
+impl AsmWriter {
+    pub fn from(symbol_table: SymbolTable) -> AsmWriter {
+        AsmWriter {
+            line_count: 0,
+            branch_count: 0,
+            call_count: 0,
+            symbol_table,
+            current_function: None,
+            comments: true,
+            headers: true,
+            comment_base: 0,
+            register_names: RegisterNames::Mixed,
+            inline_comparisons: false,
+            comparison_called: false,
+            share_comparisons: false,
+            comparison_blocks: HashMap::new(),
+            sp_base: 256,
+            asm_lines: 0,
+            source_file: String::new(),
+            source_map: None,
+        }
+    }
+
+    //Where write_init points SP, 256 unless an alternate memory map needs otherwise
+    pub fn set_sp_base(&mut self, sp_base: u16) {
+        self.sp_base = sp_base;
+    }
+
+    //By default eq/gt/lt call a shared routine; inline mode expands each one in place
+    pub fn set_inline_comparisons(&mut self, inline_comparisons: bool) {
+        self.inline_comparisons = inline_comparisons;
+    }
+
+    //With inline comparisons, writes each kind out once per file and has the rest jump to it
+    pub fn set_share_comparisons(&mut self, share_comparisons: bool) {
+        self.share_comparisons = share_comparisons;
+    }
+
+    //Controls whether the VM source is echoed as a comment above each command
+    pub fn set_comments(&mut self, comments: bool) {
+        self.comments = comments;
+    }
+
+    //Controls the //Command #n line written before each command
+    pub fn set_headers(&mut self, headers: bool) {
+        self.headers = headers;
+    }
+
+    //Number the //Command #n lines from 1 instead of 0. command_count isn't affected
+    pub fn set_comment_base(&mut self, comment_base: u16) {
+        self.comment_base = comment_base;
+    }
+
+    pub fn set_register_names(&mut self, register_names: RegisterNames) {
+        self.register_names = register_names;
+    }
+
+    //Strict overrides the comment and register settings. Emulator leaves them as they are
+    pub fn set_target(&mut self, target: Target) {
+        if target == Target::Strict {
+            self.comments = false;
+            self.headers = false;
+            self.register_names = RegisterNames::Numeric;
+        }
+    }
+
+    //Record which assembly lines each source command is written to
+    pub fn set_source_map(&mut self, enabled: bool) {
+        self.source_map = if enabled { Some(vec![]) } else { None };
+    }
+
+    //The .vm file the following source commands come from, for the source map
+    pub fn set_source_file(&mut self, file: &str) {
+        //A file's comparisons can't jump into blocks written in another one
+        self.comparison_blocks.clear();
+        self.source_file = String::from(file);
+    }
+
+    pub fn source_map(&self) -> Option<&[SourceMapEntry]> {
+        self.source_map.as_deref()
+    }
+
+    //Starts a new program: command numbers, labels and the shared routines begin again from
+    //scratch. Settings and the symbol table are kept, so statics keep their addresses
+    pub fn reset(&mut self) {
+        self.line_count = 0;
+        self.branch_count = 0;
+        self.call_count = 0;
+        self.current_function = None;
+        self.comparison_called = false;
+        self.comparison_blocks.clear();
+        self.asm_lines = 0;
+        if let Some(entries) = self.source_map.as_mut() {
+            entries.clear();
+        }
+    }
+
+    //Segment bases plus every static allocated so far
+    pub fn symbol_table(&self) -> &SymbolTable {
+        &self.symbol_table
+    }
+
+    //Commands written so far
+    pub fn command_count(&self) -> usize {
+        self.line_count
+    }
+
+    //Comparison and shift branches generated so far
+    pub fn branch_count(&self) -> usize {
+        self.branch_count
+    }
+
+    //Lines of assembly written so far, comments and labels included
+    pub fn asm_lines(&self) -> usize {
+        self.asm_lines
+    }
+
+    pub fn write_init(&mut self) -> Result<String, VmError> {
+        let stepvec = [
+            format!("@{}\nD=A\n@SP\nM=D\n", self.sp_base),
+            self.write_call("Sys.init", 0)?,
+        ];
+        let init = self.normalize_registers(stepvec.join(""));
+        self.asm_lines += init.lines().count();
+        Ok(init)
+    }
+
+    pub fn write_routines(&mut self) -> String {
+        //The shared comparison routines, written once after the last command.
+        //They are jumped over so falling off the end of the program doesn't run them.
+        if !self.comparison_called {
+            return String::new();
+        }
+        let mut stepvec = vec![AsmWriter::jump_to("COMPARE_END")];
+        for (name, instruction) in &[("EQ", "JEQ"), ("GT", "JGT"), ("LT", "JLT")] {
+            stepvec.push(format!("(COMPARE_{})\n", name));
+            stepvec.push(AsmWriter::get_operands());
+            stepvec.push(AsmWriter::write_comparison(&format!("COMPARE_{}_BRANCH", name), instruction));
+            stepvec.push(String::from("@R15\nA=M\n0;JMP\n"));
+        }
+        stepvec.push(String::from("(COMPARE_END)\n"));
+        let routines = self.normalize_registers(stepvec.join(""));
+        self.asm_lines += routines.lines().count();
+        routines
+    }
+
+    //Translates one command to assembly, for callers that keep their own commands. It is
+    //counted and scoped exactly as if it had been written through write_command
+    pub fn emit(&mut self, command: &Command) -> Result<String, VmError> {
+        let mut out: Vec<u8> = vec![];
+        self.write_entry(command, None, &mut out)?;
+        Ok(String::from_utf8_lossy(&out).into_owned())
+    }
+
+    pub fn write_command(&mut self, command: Command) -> Result<String, VmError> {
+        self.emit(&command)
+    }
+
+    pub fn write_command_to<W: Write>(&mut self, command: Command, out: &mut W) -> Result<(), VmError> {
+        self.write_entry(&command, None, out)
+    }
+
+    pub fn write_source_command_to<W: Write>(
+        &mut self,
+        command: SourceCommand,
+        out: &mut W,
+    ) -> Result<(), VmError> {
+        self.write_entry(&command.command, Some((&command.source, command.line)), out)
+    }
+
+    fn write_entry<W: Write>(
+        &mut self,
+        command: &Command,
+        source: Option<(&str, usize)>,
+        out: &mut W,
+    ) -> Result<(), VmError> {
+        let mapped = match (&self.source_map, source) {
+            (Some(_), Some((_, line))) => Some((command.clone(), line)),
+            _ => None,
+        };
+        let comm = match command {
+            Command::Push {
+                segment,
+                index,
+                class_name,
+            } => self.write_push(segment, *index, class_name)?,
+            Command::Pop {
+                segment,
+                index,
+                class_name,
+            } => self.write_pop(segment, *index, class_name)?,
+            Command::Arithmetic(token_type) => self.write_arithmetic(*token_type, source.map_or(0, |(_, line)| line))?,
+            Command::If(label) => self.write_if(label)?,
+            Command::Goto(label) => self.write_goto(label)?,
+            Command::Label(label) => self.write_label(label)?,
+            Command::Call { symbol, nargs } => self.write_call(symbol, *nargs)?,
+            Command::Function { symbol, nvars } => self.write_function(symbol, *nvars)?,
+            Command::Return => self.write_return()?,
+            Command::TailCall { symbol, nargs } => self.write_tail_call(symbol, *nargs)?,
+            Command::Store {
+                segment,
+                index,
+                value,
+                class_name,
+            } => self.write_store(segment, *index, *value, class_name)?,
+        };
+        let mut text = String::new();
+        if self.headers {
+            text.push_str(&format!("//Command #{}\n", self.line_count + self.comment_base as usize));
+        }
+        if let Some((source, _)) = source {
+            if self.comments {
+                text.push_str(&format!("// {}\n", source));
+            }
+        }
+        text.push_str(&self.normalize_registers(comm));
+        out.write_all(text.as_bytes())?;
+        self.line_count += 1;
+
+        let start = self.asm_lines + 1;
+        self.asm_lines += text.lines().count();
+        if let (Some(entries), Some((command, line))) = (self.source_map.as_mut(), mapped) {
+            entries.push(SourceMapEntry {
+                start,
+                end: self.asm_lines,
+                file: self.source_file.clone(),
+                line,
+                command,
+            });
+        }
+        Ok(())
+    }
+
+    fn write_push(
+        &mut self,
+        segment: &str,
+        index: u16,
+        class_name: &str,
+    ) -> Result<String, VmError> {
+        let stepvec: Vec<String>;
+        let seg: Address;
+        AsmWriter::check_index(segment, index, class_name)?;
+        if segment == "constant" && (index <= 1 || index as i16 == -1) {
+            //-1, 0 and 1 are valid comp constants, so they can be written straight to the stack
+            stepvec = vec![format!("@SP\nAM=M+1\nA=A-1\nM={}\n", index as i16)];
+        } else if segment == "constant" {
+            stepvec = vec![AsmWriter::constant_to_d(index), AsmWriter::push_from_d()];
+        } else if segment == "static" {
+            stepvec = vec![
+                format!("@{}\nD=M\n", self.static_address(class_name, index)?),
+                AsmWriter::push_from_d(),
+            ]
+        } else if segment == "pointer" {
+            stepvec = vec![
+                format!("@{}\nD=M\n", AsmWriter::pointer_register(index, class_name)?),
+                AsmWriter::push_from_d(),
+            ]
+        } else {
+            seg = match self.symbol_table.get_address(segment) {
+                Some(address) => *address,
+                None => {
+                    return Err(VmError::InvalidSegment {
+                        segment: String::from(segment),
+                        file: String::from(class_name),
+                    })
+                }
+            };
+            match seg {
+                Address::Relative(addr) => {
+                    stepvec = vec![
+                        AsmWriter::value_from_segment_to_a(addr, index),
+                        AsmWriter::push_from_a(),
+                    ]
+                }
+                Address::Absolute(addr) => {
+                    stepvec = vec![
+                        format!("@{}\nA=M\n", self.register(addr + index)),
+                        AsmWriter::push_from_a(),
+                    ]
+                }
+            };
+        }
+        Ok(stepvec.join(""))
+    }
+
+    fn write_pop(
+        &mut self,
+        segment: &str,
+        index: u16,
+        class_name: &str,
+    ) -> Result<String, VmError> {
+        self.write_d_to_segment(segment, index, class_name, AsmWriter::write_pop_to_d())
+    }
+
+    fn write_store(
+        &mut self,
+        segment: &str,
+        index: u16,
+        value: u16,
+        class_name: &str,
+    ) -> Result<String, VmError> {
+        let load = AsmWriter::constant_to_d(value);
+        self.write_d_to_segment(segment, index, class_name, load)
+    }
+
+    fn write_d_to_segment(
+        &mut self,
+        segment: &str,
+        index: u16,
+        class_name: &str,
+        load_d: String,
+    ) -> Result<String, VmError> {
+        //load_d is the code that puts the value to store in D
+        let stepvec: Vec<String>;
+        let seg: Address;
+        AsmWriter::check_index(segment, index, class_name)?;
+        if segment == "constant" {
+            return Err(VmError::PopToConstant { file: String::from(class_name) });
+        } else if segment == "static" {
+            stepvec = vec![
+                load_d,
+                format!("@{}\nM=D\n", self.static_address(class_name, index)?),
+            ]
+        } else if segment == "pointer" {
+            stepvec = vec![
+                load_d,
+                format!("@{}\nM=D\n", AsmWriter::pointer_register(index, class_name)?),
+            ]
+        } else {
+            seg = match self.symbol_table.get_address(segment) {
+                Some(address) => *address,
+                None => {
+                    return Err(VmError::InvalidSegment {
+                        segment: String::from(segment),
+                        file: String::from(class_name),
+                    })
+                }
+            };
+            match seg {
+                Address::Relative(addr) => {
+                    stepvec = vec![
+                        AsmWriter::save_segment_addr_to_r13(addr, index),
+                        load_d,
+                        AsmWriter::save_d_to_r13_segment_address(),
+                    ]
+                }
+                Address::Absolute(addr) => {
+                    stepvec = vec![
+                        load_d,
+                        format!("@{}\nM=D\n", self.register(addr + index)),
+                    ]
+                }
+            }
+        }
+        Ok(stepvec.join(""))
+    }
+
+    fn write_arithmetic(&mut self, token_type: TokenType, line: usize) -> Result<String, VmError> {
+        match token_type {
+            TokenType::Add => Ok(self.add()),
+            TokenType::Subtract => Ok(self.subtract()),
+            TokenType::And => Ok(self.and()),
+            TokenType::Or => Ok(self.or()),
+            TokenType::Not => Ok(self.not()),
+            TokenType::Negate => Ok(self.negate()),
+            TokenType::ShiftLeft => Ok(self.shift_left()),
+            TokenType::ShiftRight => Ok(self.shift_right()),
+            TokenType::Equal => Ok(self.equal()),
+            TokenType::GreaterThan => Ok(self.greater_than()),
+            TokenType::LessThan => Ok(self.less_than()),
+            _ => Err(VmError::UnknownArithmetic { file: self.source_file.clone(), line }),
+        }
+    }
+
+    fn write_call(&mut self, symbol: &str, nargs: u16) -> Result<String, VmError> {
+        let return_label = self.return_label();
+        let stepvec = [
+            format!("@{}\n", return_label),
+            AsmWriter::push_from_a(),
+            String::from("@LCL\n"),
+            AsmWriter::push_from_m(),
+            String::from("@ARG\n"),
+            AsmWriter::push_from_m(),
+            String::from("@THIS\n"),
+            AsmWriter::push_from_m(),
+            String::from("@THAT\n"),
+            AsmWriter::push_from_m(),
+            format!(
+                "@SP\nD=M\n@{}\nD=D-A\n@ARG\nM=D\n@SP\nD=M\n@LCL\nM=D\n",
+                nargs + 5
+            ),
+            AsmWriter::jump_to(symbol),
+            format!("({})\n", return_label),
+        ];
+        Ok(stepvec.join(""))
+    }
+
+    fn write_tail_call(&mut self, symbol: &str, nargs: u16) -> Result<String, VmError> {
+        //The saved frame is pushed above the new arguments, then arguments and frame are
+        //copied down to ARG together. ARG stays put, and the callee returns to our caller
+        let mut stepvec = vec![];
+        for offset in (1..=5).rev() {
+            stepvec.push(format!("@LCL\nD=M\n@{}\nA=D-A\nD=M\n", offset));
+            stepvec.push(AsmWriter::push_from_d());
+        }
+        stepvec.push(format!("@SP\nD=M\n@{}\nD=D-A\n@R13\nM=D\n@ARG\nD=M\n@R14\nM=D\n", nargs + 5));
+        for _ in 0..nargs + 5 {
+            stepvec.push(String::from("@R13\nAM=M+1\nA=A-1\nD=M\n@R14\nAM=M+1\nA=A-1\nM=D\n"));
+        }
+        stepvec.push(String::from("@R14\nD=M\n@SP\nM=D\n@LCL\nM=D\n"));
+        stepvec.push(AsmWriter::jump_to(symbol));
+        Ok(stepvec.join(""))
+    }
+
+    fn return_label(&mut self) -> String {
+        //Return addresses are labelled caller$ret.n, where n counts every call this writer
+        //has emitted. The counter never resets, so labels stay unique across files.
+        let label = format!(
+            "{}$ret.{}",
+            self.current_function.as_ref().map_or("Bootstrap", |f| f.as_str()),
+            self.call_count
+        );
+        self.call_count += 1;
+        label
+    }
+
+    fn write_function(&mut self, symbol: &str, mut nvars: u16) -> Result<String, VmError> {
+        let mut stepvec = vec![format!("({})\n", symbol)];
+        self.current_function = Some(String::from(symbol));
+        while nvars > 0 {
+            stepvec.push(self.write_push("constant", 0, "").unwrap());
+            nvars -= 1;
+        }
+        Ok(stepvec.join(""))
+    }
+
+    fn write_return(&mut self) -> Result<String, VmError> {
+        let stepvec = [String::from("@LCL\nD=M\n@R14\nM=D\n@5\nA=D-A\nD=M\n@R15\nM=D\n"),
+        self.write_pop("argument", 0, "").unwrap(),
+        String::from("@ARG\nD=M+1\n@SP\nM=D\n@R14\nAM=M-1\nD=M\n@THAT\nM=D\n@R14\nAM=M-1\nD=M\n@THIS\nM=D\n@R14\nAM=M-1\nD=M\n@ARG\nM=D\n@R14\nAM=M-1\nD=M\n@LCL\nM=D\n@R15\nA=M\n0;JMP\n")];
+
+        Ok(stepvec.join(""))
+    }
+
+    fn write_label(&self, label: &str) -> Result<String, VmError> {
+        Ok(format!("({})\n", self.scoped_label(label)))
+    }
+
+    fn write_goto(&self, label: &str) -> Result<String, VmError> {
+        Ok(AsmWriter::jump_to(&self.scoped_label(label)))
+    }
+
+    fn write_if(&mut self, label: &str) -> Result<String, VmError> {
+        let mut out = AsmWriter::write_pop_to_d();
+        out.push_str(&format!("@{}\nD;JNE\n", self.scoped_label(label)));
+        Ok(out)
+    }
+
+    //A temp register as written in an A-instruction
+    fn register(&self, addr: u16) -> String {
+        //Only R0-R15 have names. Past them, a ram segment address is just a number
+        match self.register_names {
+            RegisterNames::Symbolic if addr < 16 => format!("R{}", addr),
+            _ => addr.to_string(),
+        }
+    }
+
+    //In numeric mode, swaps each named register in an A-instruction for its address.
+    //Numbers are left alone either way, since @5 may just as well be the constant 5
+    fn normalize_registers(&self, asm: String) -> String {
+        if self.register_names != RegisterNames::Numeric {
+            return asm;
+        }
+        asm.lines()
+            .map(|line| {
+                let named = line.strip_prefix('@').and_then(|symbol| {
+                    Assembler::PREDEFINED.iter().find(|entry| entry.0 == symbol)
+                });
+                match named {
+                    Some((_, addr)) => format!("@{}\n", addr),
+                    None => format!("{}\n", line),
+                }
+            })
+            .collect()
+    }
+
+    fn scoped_label(&self, label: &str) -> String {
+        //Labels are qualified by the enclosing function as functionName$label
+        ::validator::scoped_label(self.current_function.as_deref(), label)
+    }
+
+    fn jump_to(label: &str) -> String {
+        format!("@{}\n0;JMP\n", label)
+    }
+
+    fn get_operands() -> String {
+        // Puts y in d, and x in a
+        let stepvec = [AsmWriter::write_pop_to_d(), AsmWriter::peek_next_value()];
+        stepvec.join("")
+    }
+
+    fn equal(&mut self) -> String {
+        self.comparison("EQ", "JEQ")
+    }
+
+    fn greater_than(&mut self) -> String {
+        self.comparison("GT", "JGT")
+    }
+
+    fn less_than(&mut self) -> String {
+        self.comparison("LT", "JLT")
+    }
+
+    fn comparison(&mut self, name: &str, instruction: &str) -> String {
+        let out = if self.inline_comparisons && self.share_comparisons {
+            self.shared_comparison(name, instruction)
+        } else if self.inline_comparisons {
+            let mut out = AsmWriter::get_operands();
+            out.push_str(&AsmWriter::write_comparison(&format!("BRANCH{}", self.branch_count), instruction));
+            out
+        } else {
+            //Pass the return address in R15 and jump to the shared routine
+            self.comparison_called = true;
+            format!(
+                "@COMPARE_RETURN{bcount}\nD=A\n@R15\nM=D\n@COMPARE_{name}\n0;JMP\n(COMPARE_RETURN{bcount})\n",
+                bcount = self.branch_count,
+                name = name
+            )
+        };
+        self.branch_count += 1;
+        out
+    }
+
+    fn shared_comparison(&mut self, name: &str, instruction: &str) -> String {
+        //The first comparison of a kind falls into its block, which returns through R15 like
+        //the shared routines do. Later ones of that kind just jump to it
+        let mut out = format!("@COMPARE_RETURN{}\nD=A\n@R15\nM=D\n", self.branch_count);
+        match self.comparison_blocks.get(name) {
+            Some(block) => out.push_str(&AsmWriter::jump_to(block)),
+            None => {
+                let block = format!("COMPARE_{}{}", name, self.branch_count);
+                out.push_str(&format!("({})\n", block));
+                out.push_str(&AsmWriter::get_operands());
+                out.push_str(&AsmWriter::write_comparison(&format!("{}_BRANCH", block), instruction));
+                out.push_str("@R15\nA=M\n0;JMP\n");
+                self.comparison_blocks.insert(String::from(name), block);
+            }
+        }
+        out.push_str(&format!("(COMPARE_RETURN{})\n", self.branch_count));
+        out
+    }
+
+    fn write_comparison(prefix: &str, instruction: &str) -> String {
+        // Assumes y is in D and SP points at x.
+        // x-y overflows when the operands have opposite signs, so in that case the
+        // result is decided from the signs alone and M-D is only used when they match
+        let stepvec = [
+            format!("@R13\nM=D\n@SP\nA=M\nD=M\n@{p}XNEG\nD;JLT\n", p = prefix),
+            format!("@R13\nD=M\n@{p}SAME\nD;JGE\nD=1\n@{p}CMP\n0;JMP\n", p = prefix),
+            format!("({p}XNEG)\n@R13\nD=M\n@{p}SAME\nD;JLT\nD=-1\n@{p}CMP\n0;JMP\n", p = prefix),
+            format!("({p}SAME)\n@R13\nD=M\n@SP\nA=M\nD=M-D\n({p}CMP)\n", p = prefix),
+            format!("@{p}\nD;{in}\nD=0\n@SP\nA=M\nM=D\n@SP\nM=M+1\n@{p}END\n0;JMP\n({p})\nD=-1\n@SP\nA=M\nM=D\n@SP\nM=M+1\n({p}END)\n",
+            in=instruction, p=prefix),
+        ];
+        stepvec.join("")
+    }
+
+    fn add(&self) -> String {
+        let mut out = AsmWriter::get_operands();
+        out.push_str(&format!("D=D+M\n"));
+        out.push_str(&AsmWriter::push_from_d());
+        out
+    }
+
+    fn and(&self) -> String {
+        let mut out = AsmWriter::get_operands();
+        out.push_str(&format!("D=D&M\n"));
+        out.push_str(&AsmWriter::push_from_d());
+        out
+    }
+
+    fn or(&self) -> String {
+        let mut out = AsmWriter::get_operands();
+        out.push_str(&format!("D=D|M\n"));
+        out.push_str(&AsmWriter::push_from_d());
+        out
+    }
+
+    fn subtract(&self) -> String {
+        let mut out = AsmWriter::get_operands();
+        out.push_str(&format!("D=M-D\n"));
+        out.push_str(&AsmWriter::push_from_d());
+        out
+    }
+
+    fn not(&self) -> String {
+        let mut out = AsmWriter::write_pop_to_d();
+        out.push_str(&format!("D=!D\n"));
+        out.push_str(&AsmWriter::push_from_d());
+        out
+    }
+
+    fn negate(&self) -> String {
+        let mut out = AsmWriter::write_pop_to_d();
+        out.push_str(&format!("D=-D\n"));
+        out.push_str(&AsmWriter::push_from_d());
+        out
+    }
+
+    fn shift_left(&self) -> String {
+        //Hack has no D+D, so x is doubled in place as x+x
+        String::from("@SP\nA=M-1\nD=M\nM=D+M\n")
+    }
+
+    fn shift_right(&mut self) -> String {
+        //Hack has no right shift, so each set bit of x (kept in R13) is copied one place down into R14.
+        //The sign bit can't be loaded as a mask, so it is tested with JGE instead
+        let prefix = format!("SHIFT{}", self.branch_count);
+        self.branch_count += 1;
+        let mut out = AsmWriter::write_pop_to_d();
+        out.push_str("@R13\nM=D\n@R14\nM=0\n");
+        out.push_str(&format!("@{p}B15\nD;JGE\n@16384\nD=A\n@R14\nM=D|M\n({p}B15)\n", p = prefix));
+        for bit in 1..15 {
+            out.push_str(&format!(
+                "@R13\nD=M\n@{mask}\nD=D&A\n@{p}B{bit}\nD;JEQ\n@{half}\nD=A\n@R14\nM=D|M\n({p}B{bit})\n",
+                mask = 1u16 << bit,
+                half = 1u16 << (bit - 1),
+                p = prefix,
+                bit = bit
+            ));
+        }
+        out.push_str("@R14\nD=M\n");
+        out.push_str(&AsmWriter::push_from_d());
+        out
+    }
+
+    fn static_address(&mut self, class_name: &str, index: u16) -> Result<u16, VmError> {
+        //Each Class.index static gets the next free RAM slot the first time it is used
+        let symbol = format!("{}.{}", class_name, index);
+        if let Some(Address::Absolute(addr)) = self.symbol_table.get_address(&symbol) {
+            return Ok(*addr);
+        }
+        self.symbol_table.allocate_static(&symbol)
+    }
+
+    fn check_index(segment: &str, index: u16, class_name: &str) -> Result<(), VmError> {
+        //temp is the fixed block RAM[5-12] and pointer only covers THIS and THAT. ram
+        //reaches the keyboard register at RAM[24576], the last address Hack maps
+        let size = match segment {
+            "temp" => 8,
+            "pointer" => 2,
+            "ram" => 24577,
+            _ => return Ok(()),
+        };
+        if index >= size {
+            return Err(VmError::IndexOutOfRange {
+                segment: String::from(segment),
+                index,
+                file: String::from(class_name),
+            });
+        }
+        Ok(())
+    }
+
+    fn pointer_register(index: u16, class_name: &str) -> Result<&'static str, VmError> {
+        //pointer 0 is THIS and pointer 1 is THAT
+        match index {
+            0 => Ok("THIS"),
+            1 => Ok("THAT"),
+            _ => Err(VmError::IndexOutOfRange {
+                segment: String::from("pointer"),
+                index,
+                file: String::from(class_name),
+            }),
+        }
+    }
+
+    fn value_from_segment_to_a(segment: &str, index: u16) -> String {
+        //Puts the value in A
+        format!("@{}\nD=M\n@{}\nA=D+A\nA=M\n", segment, index)
+    }
+
+    fn constant_to_d(value: u16) -> String {
+        //Puts a constant in D. @ only loads 0 to 32767, so negatives are loaded positive and
+        //negated, and -32768 (which has no positive) is built from -32767
+        match value as i16 {
+            value if value >= 0 => format!("@{}\nD=A\n", value),
+            i16::MIN => String::from("@32767\nD=-A\nD=D-1\n"),
+            value => format!("@{}\nD=-A\n", -value),
+        }
+    }
+
+    fn save_segment_addr_to_r13(segment: &str, index: u16) -> String {
+        //Takes an indexed segment address and stores it in R13
+        format!("@{}\nD=M\n@{}\nD=D+A\n@R13\nM=D\n", segment, index)
+    }
+
+    fn save_d_to_r13_segment_address() -> String {
+        //Assumes a value has been popped to D
+        String::from("@R13\nA=M\nM=D\n")
+    }
+
+    fn push_from_a() -> String {
+        //Assumes that the pushed value is in A
+        String::from("D=A\n@SP\nA=M\nM=D\n@SP\nM=M+1\n")
+    }
+
+    fn push_from_m() -> String {
+        //Assumes that the pushed value is in A
+        String::from("D=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n")
+    }
+
+    fn push_from_d() -> String {
+        //Assumes that the pushed value is in D
+        String::from("@SP\nA=M\nM=D\n@SP\nM=M+1\n")
+    }
+
+    fn write_pop_to_d() -> String {
+        //Puts the value in D
+        String::from("@SP\nAM=M-1\nD=M\n")
+    }
+
+    fn peek_next_value() -> String {
+        String::from("@SP\nAM=M-1\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_segment_addr() {
+        assert_eq!(
+            AsmWriter::save_segment_addr_to_r13("LCL", 2),
+            String::from("@LCL\nD=M\n@2\nD=D+A\n@R13\nM=D\n")
+        );
+    }
+
+    #[test]
+    fn test_push_static() {
+        let st = SymbolTable::new();
+        let mut writer = AsmWriter::from(st);
+        let out = writer.write_command(Command::Push {
+            segment: String::from("static"),
+            index: 0,
+            class_name: String::from("Main"),
+        });
+        assert_eq!(
+            out.unwrap(),
+            String::from("//Command #0\n@16\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n")
+        );
+    }
+
+    #[test]
+    fn test_dotted_function_labels() {
+        let mut writer = AsmWriter::from(SymbolTable::new());
+        let function = writer.emit(&"function Output.printInt 1".parse::<Command>().unwrap()).unwrap();
+        let call = writer.emit(&"call Math.multiply 2".parse::<Command>().unwrap()).unwrap();
+        let label = writer.emit(&"label LOOP".parse::<Command>().unwrap()).unwrap();
+        assert!(function.contains("(Output.printInt)\n"));
+        assert!(call.contains("@Math.multiply\n0;JMP\n(Output.printInt$ret.0)\n"));
+        assert!(label.contains("(Output.printInt$LOOP)\n"));
+    }
+
+    #[test]
+    fn test_push_ram() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        writer.set_register_names(RegisterNames::Symbolic);
+        let push = writer.write_push("ram", 17, "Main").unwrap();
+        let low = writer.write_push("ram", 13, "Main").unwrap();
+        assert_eq!(push, "@17\nA=M\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1\n");
+        assert!(low.starts_with("@R13\nA=M\n"));
+    }
+
+    #[test]
+    fn test_ram_past_keyboard() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        let keyboard = writer.write_push("ram", 24576, "Main").unwrap();
+        assert!(keyboard.starts_with("@24576\n"));
+        let push = writer.write_push("ram", 40000, "Main").unwrap_err();
+        assert_eq!(push.to_string(), "Main: Index 40000 is out of range for the ram segment");
+        let pop = writer.write_pop("ram", 24577, "Main").unwrap_err();
+        assert_eq!(pop.to_string(), "Main: Index 24577 is out of range for the ram segment");
+    }
+
+    #[test]
+    fn test_pop_ram() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        let pop = writer.write_pop("ram", 17, "Main").unwrap();
+        assert_eq!(pop, "@SP\nAM=M-1\nD=M\n@17\nM=D\n");
+        assert!(!writer.symbol_table().contains("Main.17"));
+    }
+
+    #[test]
+    fn test_labels_scoped_to_current_function() {
+        let mut writer = AsmWriter::from(SymbolTable::new());
+        let top = writer.write_command(Command::Label(String::from("LOOP"))).unwrap();
+        assert!(top.contains("(LOOP)\n"));
+        writer
+            .write_command(Command::Function { symbol: String::from("Main.f"), nvars: 0 })
+            .unwrap();
+        let inner = writer.write_command(Command::Goto(String::from("LOOP"))).unwrap();
+        assert!(inner.contains("@Main.f$LOOP\n"));
+        let inner = writer.write_command(Command::Label(String::from("LOOP"))).unwrap();
+        assert!(inner.contains("(Main.f$LOOP)\n"));
+    }
+
+    #[test]
+    fn test_push_static_reads_value() {
+        let st = SymbolTable::new();
+        let mut writer = AsmWriter::from(st);
+        let out = writer
+            .write_command(Command::Push {
+                segment: String::from("static"),
+                index: 3,
+                class_name: String::from("Main"),
+            })
+            .unwrap();
+        assert!(out.contains("@16\nD=M\n"));
+        assert!(!out.contains("A=M\nD=A"));
+    }
+
+    #[test]
+    fn test_pop_static() {
+        let st = SymbolTable::new();
+        let mut writer = AsmWriter::from(st);
+        let out = writer.write_command(Command::Pop {
+            segment: String::from("static"),
+            index: 0,
+            class_name: String::from("Main"),
+        });
+        assert_eq!(
+            out.unwrap(),
+            String::from("//Command #0\n@SP\nAM=M-1\nD=M\n@16\nM=D\n")
+        );
+    }
+
+    #[test]
+    fn test_static_allocation() {
+        let st = SymbolTable::new();
+        let mut writer = AsmWriter::from(st);
+        assert_eq!(writer.static_address("Main", 0).unwrap(), 16);
+        assert_eq!(writer.static_address("Main", 1).unwrap(), 17);
+        assert_eq!(writer.static_address("Other", 0).unwrap(), 18);
+        assert_eq!(writer.static_address("Main", 0).unwrap(), 16);
+        assert_eq!(writer.symbol_table.get_free_address(), 19);
+    }
+
+    #[test]
+    fn test_static_round_trip() {
+        let st = SymbolTable::new();
+        let mut writer = AsmWriter::from(st);
+        let pop = writer.write_pop("static", 5, "Main").unwrap();
+        let push = writer.write_push("static", 5, "Main").unwrap();
+        assert!(pop.ends_with("@16\nM=D\n"));
+        assert!(push.starts_with("@16\nD=M\n"));
+    }
+
+    #[test]
+    fn test_push_pointer() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        for (index, register) in [(0, "THIS"), (1, "THAT")] {
+            let out = writer.write_push("pointer", index, "");
+            assert_eq!(
+                out.unwrap(),
+                format!("@{}\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n", register)
+            );
+        }
+    }
+
+    #[test]
+    fn test_pop_pointer() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        for (index, register) in [(0, "THIS"), (1, "THAT")] {
+            let out = writer.write_pop("pointer", index, "");
+            assert_eq!(out.unwrap(), format!("@SP\nAM=M-1\nD=M\n@{}\nM=D\n", register));
+        }
+    }
+
+    #[test]
+    fn test_pointer_index_out_of_range() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        assert!(writer.write_push("pointer", 2, "").is_err());
+        assert!(writer.write_pop("pointer", 2, "").is_err());
+    }
+
+    #[test]
+    fn test_temp_bounds() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        let push = writer.write_push("temp", 7, "");
+        let pop = writer.write_pop("temp", 8, "");
+        assert_eq!(push.unwrap(), String::from("@12\nA=M\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1\n"));
+        assert!(matches!(pop, Err(VmError::IndexOutOfRange { index: 8, .. })));
+    }
+
+    #[test]
+    fn test_writer_errors() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        let pop_constant = writer.write_command(Command::Pop {
+            segment: String::from("constant"),
+            index: 0,
+            class_name: String::new(),
+        });
+        let bad_segment = writer.write_command(Command::Push {
+            segment: String::from("heap"),
+            index: 0,
+            class_name: String::from("Ball"),
+        });
+        assert!(matches!(pop_constant, Err(VmError::PopToConstant { .. })));
+        assert!(matches!(bad_segment, Err(VmError::InvalidSegment { ref segment, .. }) if segment == "heap"));
+        assert_eq!(bad_segment.unwrap_err().to_string(), "Ball: Invalid segment provided: heap");
+        writer.set_source_file("Ball.vm");
+        let source = SourceCommand { command: Command::Arithmetic(TokenType::Push), source: String::from("push"), line: 4 };
+        let unknown = writer.write_source_command_to(source, &mut ::std::io::sink()).unwrap_err();
+        assert_eq!(unknown.to_string(), "Ball.vm: Invalid arithmetic command at line 4");
+    }
+
+    #[test]
+    fn test_push_small_constants() {
+        let mut writer = AsmWriter::from(SymbolTable::new());
+        let push = |writer: &mut AsmWriter, index| writer.write_push("constant", index, "").unwrap();
+        let zero = push(&mut writer, 0);
+        let one = push(&mut writer, 1);
+        let two = push(&mut writer, 2);
+        assert_eq!(zero, "@SP\nAM=M+1\nA=A-1\nM=0\n");
+        assert_eq!(one, "@SP\nAM=M+1\nA=A-1\nM=1\n");
+        assert_eq!(two, "@2\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1\n");
+
+        let mut ram = [0i16; 260];
+        ram[0] = 256;
+        ram[256] = 9;
+        execute(&format!("{}{}{}", zero, one, two), &mut ram);
+        assert_eq!(ram[0], 259);
+        assert_eq!(&ram[256..259], &[0, 1, 2]);
+    }
+
+    #[test]
+    fn test_push_negative_constants() {
+        let mut writer = AsmWriter::from(SymbolTable::new());
+        let mut asm = String::new();
+        for value in &[-1i16, -32768, -5] {
+            asm.push_str(&writer.write_push("constant", *value as u16, "").unwrap());
+        }
+        assert!(asm.starts_with("@SP\nAM=M+1\nA=A-1\nM=-1\n"));
+
+        let mut ram = vec![0i16; 32768];
+        ram[0] = 256;
+        execute(&asm, &mut ram);
+        assert_eq!(ram[0], 259);
+        assert_eq!(&ram[256..259], &[-1, -32768, -5]);
+    }
+
+    #[test]
+    fn test_if_goto_jumps_on_nonzero() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        let push = writer
+            .write_command(Command::Push {
+                segment: String::from("constant"),
+                index: 1,
+                class_name: String::new(),
+            })
+            .unwrap();
+        let out = writer.write_command(Command::If(String::from("LOOP")));
+        assert_eq!(push, String::from("//Command #0\n@SP\nAM=M+1\nA=A-1\nM=1\n"));
+        assert_eq!(
+            out.unwrap(),
+            String::from("//Command #1\n@SP\nAM=M-1\nD=M\n@LOOP\nD;JNE\n")
+        );
+    }
+
+    #[test]
+    fn test_labels_scoped_to_function() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        let mut out = String::new();
+        for function in ["Main.first", "Main.second"] {
+            out.push_str(
+                &writer
+                    .write_command(Command::Function {
+                        symbol: String::from(function),
+                        nvars: 0,
+                    })
+                    .unwrap(),
+            );
+            out.push_str(&writer.write_command(Command::Label(String::from("LOOP"))).unwrap());
+            out.push_str(&writer.write_command(Command::Goto(String::from("LOOP"))).unwrap());
+        }
+        assert!(out.contains("(Main.first$LOOP)\n"));
+        assert!(out.contains("(Main.second$LOOP)\n"));
+        assert!(out.contains("@Main.first$LOOP\n0;JMP\n"));
+        assert!(out.contains("@Main.second$LOOP\n0;JMP\n"));
+        assert!(!out.contains("(LOOP)"));
+    }
+
+    #[test]
+    fn test_call_jumps_to_unscoped_function() {
+        let st = SymbolTable::new();
+        let mut writer = AsmWriter::from(st);
+        writer
+            .write_command(Command::Function {
+                symbol: String::from("Main.main"),
+                nvars: 0,
+            })
+            .unwrap();
+        let out = writer
+            .write_command(Command::Call {
+                symbol: String::from("Math.multiply"),
+                nargs: 2,
+            })
+            .unwrap();
+        assert!(out.contains("@Math.multiply\n0;JMP\n"));
+    }
+
+    #[test]
+    fn test_return_labels_unique() {
+        let st = SymbolTable::new();
+        let mut writer = AsmWriter::from(st);
+        let call = || Command::Call {
+            symbol: String::from("Foo"),
+            nargs: 0,
+        };
+        let first = writer.write_command(call()).unwrap();
+        let second = writer.write_command(call()).unwrap();
+        assert!(first.starts_with("//Command #0\n@Bootstrap$ret.0\n"));
+        assert!(first.ends_with("(Bootstrap$ret.0)\n"));
+        assert!(second.starts_with("//Command #1\n@Bootstrap$ret.1\n"));
+        assert!(second.ends_with("(Bootstrap$ret.1)\n"));
+    }
+
+    #[test]
+    fn test_write_command_to_buffer() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        let mut out: Vec<u8> = vec![];
+        writer.write_command_to(Command::Label(String::from("A")), &mut out).unwrap();
+        writer.write_command_to(Command::Goto(String::from("A")), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "//Command #0\n(A)\n//Command #1\n@A\n0;JMP\n"
+        );
+    }
+
+    fn push_seven() -> SourceCommand {
+        SourceCommand {
+            command: Command::Push {
+                segment: String::from("constant"),
+                index: 7,
+                class_name: String::new(),
+            },
+            source: String::from("push constant 7"),
+            line: 1,
+        }
+    }
+
+    #[test]
+    fn test_source_comment() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        let mut out: Vec<u8> = vec![];
+        writer.write_source_command_to(push_seven(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "//Command #0\n// push constant 7\n@7\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1\n"
+        );
+    }
+
+    #[test]
+    fn test_source_comment_stripped() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        writer.set_comments(false);
+        let mut out: Vec<u8> = vec![];
+        writer.write_source_command_to(push_seven(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "//Command #0\n@7\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1\n"
+        );
+    }
+
+    #[test]
+    fn test_store() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        let local = writer.write_store("local", 2, 9, "");
+        let temp = writer.write_store("temp", 1, 9, "");
+        assert_eq!(
+            local.unwrap(),
+            String::from("@LCL\nD=M\n@2\nD=D+A\n@R13\nM=D\n@9\nD=A\n@R13\nA=M\nM=D\n")
+        );
+        assert_eq!(temp.unwrap(), String::from("@9\nD=A\n@6\nM=D\n"));
+    }
+
+    #[test]
+    fn test_add() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        let out = writer.write_command(Command::Arithmetic(TokenType::Add));
+        assert_eq!(
+            out.unwrap(),
+            String::from(
+                "//Command #0\n@SP
+AM=M-1
+D=M
+@SP
+AM=M-1
+D=D+M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+"
+            )
+        );
+    }
+
+    #[test]
+    fn test_add_writer() {
+        let st = SymbolTable::new();
+        let writer = AsmWriter::from(st);
+        assert_eq!(
+            writer.add(),
+            String::from(
+                "@SP
+AM=M-1
+D=M
+@SP
+AM=M-1
+D=D+M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+"
+            )
+        );
+    }
+
+    #[test]
+    fn test_equal_writer() {
+        let st = SymbolTable::new();
+        let mut writer = AsmWriter::from(st);
+        writer.set_inline_comparisons(true);
+        let out = writer.write_command(Command::Arithmetic(TokenType::Equal));
+        assert_eq!(
+            out.unwrap(),
+            String::from(
+                "//Command #0\n@SP\nAM=M-1\nD=M\n@SP\nAM=M-1\n@R13
+M=D
+@SP
+A=M
+D=M
+@BRANCH0XNEG
+D;JLT
+@R13
+D=M
+@BRANCH0SAME
+D;JGE
+D=1
+@BRANCH0CMP
+0;JMP
+(BRANCH0XNEG)
+@R13
+D=M
+@BRANCH0SAME
+D;JLT
+D=-1
+@BRANCH0CMP
+0;JMP
+(BRANCH0SAME)
+@R13
+D=M
+@SP
+A=M
+D=M-D
+(BRANCH0CMP)
+@BRANCH0
+D;JEQ
+D=0
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@BRANCH0END
+0;JMP
+(BRANCH0)
+D=-1
+@SP
+A=M
+M=D
+@SP
+M=M+1
+(BRANCH0END)
+"
+            )
+        );
+    }
+
+    // Assembles Hack assembly and runs it on the roundtrip CPU model, so tests can check behaviour
+    fn execute(asm: &str, ram: &mut [i16]) {
+        let hack = ::assembler::Assembler::new().assemble(asm).unwrap();
+        ::roundtrip::run_hack(&hack, ram);
+    }
+
+    fn compare_with(x: i16, y: i16, token_type: TokenType, inline: bool) -> i16 {
+        let mut writer = AsmWriter::from(SymbolTable::new());
+        writer.set_inline_comparisons(inline);
+        let mut asm = writer.write_command(Command::Arithmetic(token_type)).unwrap();
+        asm.push_str(&writer.write_routines());
+        let mut ram = [0i16; 512];
+        ram[0] = 258;
+        ram[256] = x;
+        ram[257] = y;
+        execute(&asm, &mut ram);
+        assert_eq!(ram[0], 257);
+        ram[256]
+    }
+
+    #[test]
+    fn test_shared_comparisons() {
+        let write = |share: bool| {
+            let mut writer = AsmWriter::from(SymbolTable::new());
+            writer.set_inline_comparisons(true);
+            writer.set_share_comparisons(share);
+            let mut asm = String::new();
+            for _ in 0..3 {
+                asm.push_str(&writer.write_command(Command::Arithmetic(TokenType::Equal)).unwrap());
+            }
+            asm
+        };
+        let shared = write(true);
+        assert_eq!(shared.matches("D;JEQ\n").count(), 1);
+        assert_eq!(shared.matches("@COMPARE_EQ0\n0;JMP\n").count(), 2);
+        assert!(shared.lines().count() < write(false).lines().count());
+
+        //4 eq 4, then 3 eq -1, then 0 eq 0
+        let mut ram = vec![0i16; 512];
+        ram[0] = 260;
+        ram[256..260].copy_from_slice(&[0, 3, 4, 4]);
+        execute(&shared, &mut ram);
+        assert_eq!(ram[0], 257);
+        assert_eq!(ram[256], -1);
+    }
+
+    fn shift(x: i16, token_type: TokenType) -> i16 {
+        let mut writer = AsmWriter::from(SymbolTable::new());
+        let asm = writer.write_command(Command::Arithmetic(token_type)).unwrap();
+        //The masks are loaded into A, so the whole address space must be readable
+        let mut ram = vec![0i16; 32768];
+        ram[0] = 257;
+        ram[256] = x;
+        execute(&asm, &mut ram);
+        assert_eq!(ram[0], 257);
+        ram[256]
+    }
+
+    #[test]
+    fn test_shift_left() {
+        assert_eq!(shift(5, TokenType::ShiftLeft), 10);
+        assert_eq!(shift(-3, TokenType::ShiftLeft), -6);
+        assert_eq!(shift(16384, TokenType::ShiftLeft), -32768);
+    }
+
+    #[test]
+    fn test_shift_right() {
+        assert_eq!(shift(10, TokenType::ShiftRight), 5);
+        assert_eq!(shift(7, TokenType::ShiftRight), 3);
+        assert_eq!(shift(1, TokenType::ShiftRight), 0);
+        //The shift is logical, so the sign bit isn't kept
+        assert_eq!(shift(-1, TokenType::ShiftRight), 32767);
+        assert_eq!(shift(-32768, TokenType::ShiftRight), 16384);
+    }
+
+    fn compare(x: i16, y: i16, token_type: TokenType) -> i16 {
+        let inline = compare_with(x, y, token_type, true);
+        assert_eq!(compare_with(x, y, token_type, false), inline);
+        inline
+    }
+
+    #[test]
+    fn test_write_init() {
+        let mut writer = AsmWriter::from(SymbolTable::new());
+        let init = writer.write_init().unwrap();
+        assert!(init.starts_with("@256\nD=A\n@SP\nM=D\n"));
+        assert!(init.contains("@Sys.init\n0;JMP\n"));
+        assert_eq!(writer.asm_lines(), init.lines().count());
+    }
+
+    #[test]
+    fn test_sp_base() {
+        let mut writer = AsmWriter::from(SymbolTable::new());
+        writer.set_sp_base(1024);
+        assert!(writer.write_init().unwrap().starts_with("@1024\nD=A\n@SP\nM=D\n"));
+    }
+
+    #[test]
+    fn test_static_matches_symbol_table() {
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        let push = writer.write_push("static", 0, "Main").unwrap();
+        assert_eq!(writer.symbol_table().get_address("Main.0"), Some(&Address::Absolute(16)));
+        assert!(push.starts_with("@16\nD=M\n"));
+        assert!(!writer.symbol_table().contains("static"));
+    }
+
+    #[test]
+    fn test_return_register_names() {
+        let mut mixed = AsmWriter::from(SymbolTable::new());
+        mixed.symbol_table.load_starting_table();
+        let mixed = mixed.write_return().unwrap();
+        let mut numeric = AsmWriter::from(SymbolTable::new());
+        numeric.symbol_table.load_starting_table();
+        numeric.set_register_names(RegisterNames::Numeric);
+        let numeric = numeric.write_command(Command::Return).unwrap();
+
+        assert!(mixed.starts_with("@LCL\nD=M\n@R14\nM=D\n@5\nA=D-A\n"));
+        assert!(numeric.contains("@1\nD=M\n@14\nM=D\n@5\nA=D-A\n"));
+        for name in &["@SP\n", "@LCL\n", "@ARG\n", "@THIS\n", "@THAT\n", "@R13\n", "@R14\n", "@R15\n"] {
+            assert!(!numeric.contains(name));
+        }
+        assert!(numeric.contains("@3\nM=D\n") && numeric.contains("@15\nA=M\n0;JMP\n"));
+    }
+
+    #[test]
+    fn test_temp_register_names() {
+        let mut symbolic = AsmWriter::from(SymbolTable::new());
+        symbolic.symbol_table.load_starting_table();
+        symbolic.set_register_names(RegisterNames::Symbolic);
+        let push = symbolic.write_push("temp", 2, "").unwrap();
+        let pop = symbolic.write_pop("temp", 7, "").unwrap();
+        let ret = symbolic.write_return().unwrap();
+        assert!(push.starts_with("@R7\nA=M\n"));
+        assert!(pop.ends_with("@R12\nM=D\n"));
+        assert!(ret.contains("@LCL\n") && ret.contains("@R15\n"));
+    }
+
+    #[test]
+    fn test_emit_borrowed_command() {
+        let commands = [
+            Command::Function { symbol: String::from("Main.f"), nvars: 0 },
+            Command::Goto(String::from("LOOP")),
+        ];
+        let mut writer = AsmWriter::from(SymbolTable::new());
+        let out: Vec<String> = commands.iter().map(|c| writer.emit(c).unwrap()).collect();
+        assert_eq!(out[1], "//Command #1\n@Main.f$LOOP\n0;JMP\n");
+        assert_eq!(commands[1], Command::Goto(String::from("LOOP")));
+        assert_eq!(writer.command_count(), 2);
+    }
+
+    #[test]
+    fn test_comment_base() {
+        let mut writer = AsmWriter::from(SymbolTable::new());
+        writer.set_comment_base(1);
+        let first = writer.write_command(Command::Arithmetic(TokenType::Negate)).unwrap();
+        let second = writer.write_command(Command::Arithmetic(TokenType::Not)).unwrap();
+        assert!(first.starts_with("//Command #1\n"));
+        assert!(second.starts_with("//Command #2\n"));
+        assert_eq!(writer.command_count(), 2);
+    }
+
+    #[test]
+    fn test_no_headers() {
+        let mut writer = AsmWriter::from(SymbolTable::new());
+        writer.set_headers(false);
+        writer.set_comments(false);
+        let mut out: Vec<u8> = vec![];
+        writer.write_source_command_to(push_seven(), &mut out).unwrap();
+        writer.write_command_to(Command::Arithmetic(TokenType::Negate), &mut out).unwrap();
+        let asm = String::from_utf8(out).unwrap();
+        assert!(!asm.contains("//"));
+        assert!(asm.starts_with("@7\nD=A\n"));
+        assert_eq!(writer.command_count(), 2);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut writer = AsmWriter::from(SymbolTable::new());
+        writer.write_command(Command::Function { symbol: String::from("Main.main"), nvars: 0 }).unwrap();
+        writer.write_command(Command::Arithmetic(TokenType::Equal)).unwrap();
+        writer.write_command(Command::Push { segment: String::from("static"), index: 0, class_name: String::from("Main") }).unwrap();
+        writer.reset();
+
+        assert_eq!(writer.command_count(), 0);
+        assert_eq!(writer.write_routines(), "");
+        let call = writer.write_command(Command::Call { symbol: String::from("Main.main"), nargs: 0 }).unwrap();
+        assert!(call.starts_with("//Command #0\n@Bootstrap$ret.0\n"));
+        let eq = writer.write_command(Command::Arithmetic(TokenType::Equal)).unwrap();
+        assert!(eq.contains("(COMPARE_RETURN0)"));
+        assert_eq!(writer.symbol_table().get_address("Main.0"), Some(&Address::Absolute(16)));
+    }
+
+    #[test]
+    fn test_source_map() {
+        let mut writer = AsmWriter::from(SymbolTable::new());
+        writer.set_source_map(true);
+        writer.set_source_file("Main");
+        let commands = vec![
+            SourceCommand {
+                command: Command::Push { segment: String::from("constant"), index: 7, class_name: String::from("Main") },
+                source: String::from("push constant 7"),
+                line: 2,
+            },
+            SourceCommand { command: Command::Arithmetic(TokenType::Not), source: String::from("not"), line: 3 },
+        ];
+        let mut out: Vec<u8> = vec![];
+        for command in commands {
+            writer.write_source_command_to(command, &mut out).unwrap();
+        }
+        let asm = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = asm.lines().collect();
+
+        let map = writer.source_map().unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!((map[0].start, map[0].end, map[0].line), (1, 9, 2));
+        assert_eq!((map[1].start, map[1].end, map[1].line), (10, lines.len(), 3));
+        assert_eq!(map[1].file, "Main");
+        assert_eq!(map[1].command, Command::Arithmetic(TokenType::Not));
+        assert_eq!(lines[map[0].start - 1..map[0].end].join("\n"), "//Command #0\n// push constant 7\n@7\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1");
+        assert_eq!(lines[map[1].start], "// not");
+    }
+
+    #[test]
+    fn test_shared_comparison_routine() {
+        let mut writer = AsmWriter::from(SymbolTable::new());
+        let mut out = String::new();
+        for token_type in [TokenType::Equal, TokenType::Equal, TokenType::GreaterThan] {
+            out.push_str(&writer.write_command(Command::Arithmetic(token_type)).unwrap());
+        }
+        out.push_str(&writer.write_routines());
+        assert_eq!(out.matches("(COMPARE_EQ)\n").count(), 1);
+        assert_eq!(out.matches("(COMPARE_GT)\n").count(), 1);
+        assert_eq!(out.matches("@COMPARE_EQ\n0;JMP\n").count(), 2);
+        assert_eq!(out.matches("@COMPARE_GT\n0;JMP\n").count(), 1);
+        assert_eq!(out.matches("D=M-D\n").count(), 3);
+    }
+
+    #[test]
+    fn test_greater_than_overflow() {
+        assert_eq!(compare(30000, -30000, TokenType::GreaterThan), -1);
+        assert_eq!(compare(-30000, 30000, TokenType::GreaterThan), 0);
+    }
+
+    #[test]
+    fn test_comparisons_same_sign() {
+        assert_eq!(compare(5, 3, TokenType::GreaterThan), -1);
+        assert_eq!(compare(-5, -3, TokenType::LessThan), -1);
+        assert_eq!(compare(0, -1, TokenType::Equal), 0);
+        assert_eq!(compare(-7, -7, TokenType::Equal), -1);
+        assert_eq!(compare(32767, -32768, TokenType::LessThan), 0);
+    }
+
+    #[test]
+    fn test_tail_call() {
+        let argument = |index| Command::Push { segment: String::from("argument"), index, class_name: String::new() };
+        let constant = |index| Command::Push { segment: String::from("constant"), index, class_name: String::new() };
+        let tail_call = || Command::TailCall { symbol: String::from("Main.sum"), nargs: 2 };
+        let commands = vec![
+            Command::Call { symbol: String::from("Main.start"), nargs: 0 },
+            Command::Pop { segment: String::from("temp"), index: 0, class_name: String::new() },
+            Command::Goto(String::from("HALT")),
+            //Main.start has no arguments, so the tail call grows the argument block
+            Command::Function { symbol: String::from("Main.start"), nvars: 0 },
+            constant(10),
+            constant(0),
+            tail_call(),
+            //Main.sum(n, acc) adds n..1 to acc
+            Command::Function { symbol: String::from("Main.sum"), nvars: 0 },
+            argument(0),
+            constant(0),
+            Command::Arithmetic(TokenType::Equal),
+            Command::If(String::from("BASE")),
+            argument(0),
+            constant(1),
+            Command::Arithmetic(TokenType::Subtract),
+            argument(1),
+            argument(0),
+            Command::Arithmetic(TokenType::Add),
+            tail_call(),
+            Command::Label(String::from("BASE")),
+            argument(1),
+            Command::Return,
+        ];
+        let mut st = SymbolTable::new();
+        st.load_starting_table();
+        let mut writer = AsmWriter::from(st);
+        let mut asm = String::new();
+        for command in commands {
+            asm.push_str(&writer.write_command(command).unwrap());
+        }
+        asm.push_str(&writer.write_routines());
+        asm.push_str("(HALT)\n");
+        //Only the first call pushes a return address
+        assert_eq!(asm.matches("$ret.").count(), 2);
+        assert_eq!(asm.matches("@Main.sum\n0;JMP\n").count(), 2);
+
+        let mut ram = vec![0i16; 32768];
+        ram[0] = 256;
+        execute(&asm, &mut ram);
+        assert_eq!(ram[5], 55);
+        assert_eq!(ram[0], 256);
+    }
+}